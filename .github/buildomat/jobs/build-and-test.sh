@@ -33,3 +33,4 @@ done
 
 banner test
 cargo test
+cargo test --all-features
@@ -5,10 +5,19 @@
 // Copyright 2022 Oxide Computer Company
 
 use anyhow::Result;
-use clap::{AppSettings, Parser};
+use clap::{AppSettings, Parser, ValueEnum};
 use colored::*;
-use devinfo::get_devices;
-use std::io::{stdout, Write};
+use devinfo::{
+    can_fetch_prom, for_each_device, get_device_tree, get_devices,
+    load_devices, save_devices, DevInfoError, DevInfoWarning, DeviceInfo,
+    DeviceKey, DeviceNode, DeviceQuery, DiPropValue, PropPredicate, Radix,
+    SnapshotFlags, SpecType, TreeSummary,
+};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{stdout, BufReader, BufWriter, Write};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
 use tabwriter::TabWriter;
 
 #[derive(Parser)]
@@ -27,8 +36,17 @@ struct Opts {
 
 #[derive(Parser)]
 enum SubCommand {
-    /// Show device information. All numeric values in hex.
+    /// Show device information. Numeric values are in hex unless
+    /// `--decimal` is given, except for `#`-prefixed count properties such
+    /// as `#address-cells`, which are always decimal.
     Show(Show),
+    /// Show the device hierarchy, indented by depth.
+    Tree(Tree),
+    /// Count devices by driver, most common first.
+    Count(Count),
+    /// Save every device to a file, to be examined later, possibly on
+    /// another machine, with `show --load`.
+    Save(Save),
 }
 
 struct I32(i32);
@@ -45,12 +63,31 @@ impl std::str::FromStr for I32 {
     }
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Aligned, colored property tables.
+    Table,
+    /// A JSON object keyed by device name.
+    Json,
+    /// Uncolored `name: value` lines, suitable for logs.
+    Plain,
+    /// One tab-separated `device property value` line per property, for
+    /// grep and awk.
+    Kv,
+}
+
 #[derive(Parser)]
 #[clap(setting = AppSettings::InferSubcommands)]
 struct Show {
     /// Filter by device name.
     filter: Option<String>,
 
+    /// Treat the name filter as a regular expression rather than an exact
+    /// name, e.g. `^pci`.
+    #[cfg(feature = "regex")]
+    #[clap(long)]
+    regex: bool,
+
     /// Filter by device id (hex values only).
     #[clap(short, long)]
     id: Option<I32>,
@@ -59,67 +96,344 @@ struct Show {
     #[clap(short, long)]
     vendor: Option<I32>,
 
+    /// Only show devices that have the named property, whatever its value.
+    /// May be given more than once, in which case every property must be
+    /// present.
+    #[clap(long, value_name = "PROPNAME")]
+    has: Vec<String>,
+
+    /// Only show devices bound to the named driver, e.g. `nvme`.
+    #[clap(long)]
+    driver: Option<String>,
+
     /// Fetch device prom data (requires root privilege)
     #[clap(short, long)]
     prom: bool,
+
+    /// Show numeric values in decimal rather than hex.
+    #[clap(short, long)]
+    decimal: bool,
+
+    /// Include minor node data in the snapshot, and list each device's
+    /// minor nodes after its properties.
+    #[clap(short, long)]
+    minors: bool,
+
+    /// Include multipath information in the snapshot, and list the paths to
+    /// each multipath device after its properties. This typically requires
+    /// root privilege.
+    #[clap(long)]
+    paths: bool,
+
+    /// List each device's compatible names, most specific first, along
+    /// with the name it was bound by.
+    #[clap(short, long)]
+    compatible: bool,
+
+    /// Output format.
+    #[clap(short = 'o', long, value_enum, default_value = "table")]
+    format: Format,
+
+    /// Show devices from a file written by `devadm save` rather than from
+    /// this machine.
+    #[clap(long)]
+    load: Option<PathBuf>,
+
+    /// Only walk the subtree rooted at this `/devices` path, e.g.
+    /// `/pci@0,0`, which is much faster than walking the whole tree.
+    #[clap(long, value_name = "DEVFS-PATH", conflicts_with = "load")]
+    root: Option<String>,
+}
+
+#[derive(Parser)]
+struct Tree {}
+
+#[derive(Parser)]
+struct Count {
+    /// Count devices by inferred class, e.g. storage or network, rather
+    /// than by driver.
+    #[clap(long)]
+    class: bool,
+}
+
+#[derive(Parser)]
+struct Save {
+    /// The file to write.
+    file: PathBuf,
+
+    /// Include device prom data (requires root privilege)
+    #[clap(short, long)]
+    prom: bool,
 }
 
 fn main() -> Result<()> {
     let opts: Opts = Opts::parse();
     match opts.subcmd {
         SubCommand::Show(ref s) => show_devices(&opts, s),
+        SubCommand::Tree(ref t) => show_tree(&opts, t),
+        SubCommand::Count(ref c) => show_count(&opts, c),
+        SubCommand::Save(ref s) => save(&opts, s),
+    }
+}
+
+fn save(_opts: &Opts, s: &Save) -> Result<()> {
+    let devices = get_devices(s.prom)?;
+    let mut w = BufWriter::new(File::create(&s.file)?);
+    save_devices(&devices, &mut w)?;
+    w.flush()?;
+    Ok(())
+}
+
+fn show_count(_opts: &Opts, c: &Count) -> Result<()> {
+    // Walk rather than collect a device map: nodes that share a key, such
+    // as identical functions behind different ports, would collapse into
+    // one map entry.
+    let mut summary = TreeSummary::default();
+    for_each_device(false, |_, dev_info| {
+        summary.add(dev_info);
+        ControlFlow::Continue(())
+    })?;
+
+    let (heading, mut counts) = if c.class {
+        let counts: Vec<_> = summary
+            .by_class
+            .iter()
+            .map(|(class, count)| {
+                (format!("{:?}", class).to_lowercase(), *count)
+            })
+            .collect();
+        ("class", counts)
+    } else {
+        let mut counts: Vec<_> = summary
+            .drivers_by_count()
+            .into_iter()
+            .map(|(name, count)| (name.to_owned(), count))
+            .collect();
+        if summary.no_driver > 0 {
+            counts.push(("(none)".to_owned(), summary.no_driver));
+        }
+        ("driver", counts)
+    };
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    let mut tw = TabWriter::new(stdout());
+    writeln!(&mut tw, "{}\t{}", "count".dimmed(), heading.dimmed())?;
+    writeln!(
+        &mut tw,
+        "{}\t{}",
+        "-----".bright_black(),
+        "-".repeat(heading.len()).bright_black(),
+    )?;
+    for (name, count) in counts {
+        writeln!(&mut tw, "{}\t{}", count, name)?;
+    }
+    tw.flush()?;
+
+    Ok(())
+}
+
+fn show_tree(_opts: &Opts, _t: &Tree) -> Result<()> {
+    let tree = get_device_tree(false)?;
+    print_node(&tree.root, 0);
+    Ok(())
+}
+
+fn print_node(node: &DeviceNode, depth: usize) {
+    println!("{}{}", "    ".repeat(depth), node.key);
+    for child in &node.children {
+        print_node(child, depth + 1);
     }
 }
 
 fn show_devices(_opts: &Opts, s: &Show) -> Result<()> {
-    let info = get_devices(s.prom)?;
+    if s.prom && s.load.is_none() && !can_fetch_prom() {
+        anyhow::bail!(
+            "--prom requires root privilege (cannot open /dev/openprom)"
+        );
+    }
 
+    let name_filter = NameFilter::new(s)?;
+    let mut info = match &s.load {
+        Some(path) => load_devices(BufReader::new(File::open(path)?))?,
+        None => walk_devices(s)?,
+    };
+    if let Some(driver) = &s.driver {
+        info.retain(|_, i| i.driver_name.as_ref() == Some(driver));
+    }
+
+    let mut predicates = Vec::new();
+    if let Some(id) = &s.id {
+        predicates.push(PropPredicate::new(
+            "device-id",
+            DiPropValue::Ints(vec![id.0]),
+        ));
+    }
+    if let Some(vendor) = &s.vendor {
+        predicates.push(PropPredicate::new(
+            "vendor-id",
+            DiPropValue::Ints(vec![vendor.0]),
+        ));
+    }
+
+    let mut devices = BTreeMap::new();
     for (key, dev_info) in info {
-        match &s.filter {
-            Some(f) => {
-                if !key.node_name.eq(f) {
-                    continue;
+        if !name_filter.matches(&key.node_name) {
+            continue;
+        }
+
+        if !dev_info.matches(&predicates) {
+            continue;
+        }
+
+        if !s.has.iter().all(|name| dev_info.props.contains_key(name)) {
+            continue;
+        }
+
+        devices.insert(key, dev_info);
+    }
+
+    let radix = if s.decimal {
+        Radix::Decimal
+    } else {
+        Radix::Hex
+    };
+    match s.format {
+        Format::Table => show_table(devices, s, radix),
+        Format::Json => {
+            serde_json::to_writer_pretty(stdout(), &devices)?;
+            println!();
+            Ok(())
+        }
+        Format::Kv => {
+            for (key, dev_info) in devices {
+                for (prop_name, value) in dev_info.props {
+                    let radix = radix.for_prop(&prop_name);
+                    println!("{}\t{}\t{}", key, prop_name, value.format(radix));
                 }
+                print_warnings(&dev_info.warnings);
             }
-            None => {}
+            Ok(())
         }
+        Format::Plain => {
+            for (key, dev_info) in devices {
+                println!("{}", key);
+                println!("{}", dev_info.display(radix));
+                print_warnings(&dev_info.warnings);
+            }
+            Ok(())
+        }
+    }
+}
 
-        match &s.id {
-            Some(id) => match dev_info.props.get("device-id") {
-                Some(value) => {
-                    if !value.matches_int(id.0) {
-                        continue;
-                    }
-                }
-                None => {
-                    continue;
-                }
-            },
-            None => {}
+fn walk_devices(s: &Show) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    let root = s.root.as_deref().unwrap_or("/");
+    if !root.starts_with('/') {
+        anyhow::bail!("--root must be a /devices path such as /pci@0,0");
+    }
+
+    let query = DeviceQuery::new()
+        .root(root)
+        .flags(snapshot_flags(s))
+        .fetch_prom(s.prom);
+    match query.run() {
+        Ok(devices) => Ok(devices),
+        Err(DevInfoError::NotFound(path)) => {
+            anyhow::bail!("--root {}: no such device", path)
         }
+        Err(e) => Err(e.into()),
+    }
+}
 
-        match &s.vendor {
-            Some(vendor) => match dev_info.props.get("vendor-id") {
-                Some(value) => {
-                    if !value.matches_int(vendor.0) {
-                        continue;
-                    }
-                }
-                None => {
-                    continue;
-                }
-            },
-            None => {}
+/// The snapshot detail `show` needs. Properties are always wanted, minor
+/// and multipath data only when asked for.
+fn snapshot_flags(s: &Show) -> SnapshotFlags {
+    let mut flags = SnapshotFlags::SUBTREE | SnapshotFlags::PROP;
+    if s.minors {
+        flags |= SnapshotFlags::MINOR;
+    }
+    if s.paths {
+        flags |= SnapshotFlags::PATH;
+    }
+    flags
+}
+
+/// `show`'s name filter, which is an exact match unless `--regex` is given.
+enum NameFilter {
+    Any,
+    Exact(String),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl NameFilter {
+    fn new(s: &Show) -> Result<NameFilter> {
+        let f = match &s.filter {
+            Some(f) => f,
+            None => return Ok(NameFilter::Any),
+        };
+
+        #[cfg(feature = "regex")]
+        if s.regex {
+            return Ok(NameFilter::Regex(regex::Regex::new(f)?));
+        }
+
+        Ok(NameFilter::Exact(f.clone()))
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            NameFilter::Any => true,
+            NameFilter::Exact(f) => name == f,
+            #[cfg(feature = "regex")]
+            NameFilter::Regex(re) => re.is_match(name),
         }
+    }
+}
+
+/// Report warnings on stderr, so that they don't get mixed into output
+/// meant for other programs.
+fn print_warnings(warnings: &[DevInfoWarning]) {
+    for warning in warnings {
+        eprintln!("{}: {}", "warning".yellow(), warning);
+    }
+}
+
+/// The bound driver and instance number for a device's header, e.g.
+/// `[driver nvme, instance 3]`, leaving out whichever the device doesn't
+/// have.
+fn header_context(dev_info: &DeviceInfo) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(driver) = &dev_info.driver_name {
+        parts.push(format!("driver {}", driver));
+    }
+    if dev_info.instance >= 0 {
+        parts.push(format!("instance {}", dev_info.instance));
+    }
+    if parts.is_empty() {
+        None
+    } else {
+        Some(format!("[{}]", parts.join(", ")))
+    }
+}
 
-        let label = match key.unit_address {
-            Some(a) => {
-                format!("{}@{}", key.node_name, a)
+fn show_table(
+    devices: BTreeMap<DeviceKey, DeviceInfo>,
+    s: &Show,
+    radix: Radix,
+) -> Result<()> {
+    for (key, dev_info) in devices {
+        let label = key.to_string();
+        match header_context(&dev_info) {
+            Some(context) => {
+                println!("{} {}", label.bright_blue().bold(), context);
+                let width = label.len() + 1 + context.len();
+                println!("{}", "=".repeat(width).bright_black());
             }
-            None => key.node_name.clone(),
-        };
-        println!("{}", label.bright_blue().bold());
-        println!("{}", "=".repeat(label.len()).bright_black());
+            None => {
+                println!("{}", label.bright_blue().bold());
+                println!("{}", "=".repeat(label.len()).bright_black());
+            }
+        }
 
         let mut tw = TabWriter::new(stdout());
         writeln!(&mut tw, "{}\t{}", "property".dimmed(), "value".dimmed())?;
@@ -130,11 +444,109 @@ fn show_devices(_opts: &Opts, s: &Show) -> Result<()> {
             "-----".bright_black(),
         )?;
         for (prop_name, value) in dev_info.props {
-            writeln!(&mut tw, "{}\t{}", prop_name, value)?;
+            let radix = radix.for_prop(&prop_name);
+            writeln!(&mut tw, "{}\t{}", prop_name, value.format(radix))?;
+        }
+        if s.minors && !dev_info.minors.is_empty() {
+            writeln!(&mut tw)?;
+            writeln!(
+                &mut tw,
+                "{}\t{}\t{}",
+                "minor".dimmed(),
+                "node type".dimmed(),
+                "spec type".dimmed(),
+            )?;
+            writeln!(
+                &mut tw,
+                "{}\t{}\t{}",
+                "-----".bright_black(),
+                "---------".bright_black(),
+                "---------".bright_black(),
+            )?;
+            for minor in &dev_info.minors {
+                let spec_type = match minor.spec_type {
+                    SpecType::Block => "block",
+                    SpecType::Char => "char",
+                };
+                writeln!(
+                    &mut tw,
+                    "{}\t{}\t{}",
+                    minor.name,
+                    minor.node_type.as_deref().unwrap_or("-"),
+                    spec_type,
+                )?;
+            }
+        }
+        if s.paths && !dev_info.paths.is_empty() {
+            writeln!(&mut tw)?;
+            writeln!(
+                &mut tw,
+                "{}\t{}\t{}",
+                "path".dimmed(),
+                "state".dimmed(),
+                "controller".dimmed(),
+            )?;
+            writeln!(
+                &mut tw,
+                "{}\t{}\t{}",
+                "----".bright_black(),
+                "-----".bright_black(),
+                "----------".bright_black(),
+            )?;
+            for path in &dev_info.paths {
+                writeln!(
+                    &mut tw,
+                    "{}\t{:?}\t{}",
+                    path.bus_address.as_deref().unwrap_or("-"),
+                    path.state,
+                    path.phci.as_deref().unwrap_or("-"),
+                )?;
+            }
         }
         tw.flush()?;
+        if s.compatible && !dev_info.compatible.is_empty() {
+            println!();
+            println!(
+                "{} {}",
+                "compatible, bound as".dimmed(),
+                dev_info.binding_name.as_deref().unwrap_or("-"),
+            );
+            for (i, name) in dev_info.compatible.iter().enumerate() {
+                if dev_info.binding_name.as_ref() == Some(name) {
+                    println!("{:>4}. {} {}", i + 1, name, "<- bound".green());
+                } else {
+                    println!("{:>4}. {}", i + 1, name);
+                }
+            }
+        }
+        print_warnings(&dev_info.warnings);
         println!();
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Nodes that share a key, e.g. the same NVMe function behind two root
+    /// ports, are each counted.
+    #[test]
+    fn count_shared_keys() {
+        let mut dev = DeviceInfo::new();
+        dev.node_name = "pci144d,a808".to_owned();
+        dev.driver_name = Some("nvme".to_owned());
+        let mut unbound = DeviceInfo::new();
+        unbound.binding_name = Some("pciex1022,1483".to_owned());
+
+        let mut summary = TreeSummary::default();
+        for dev_info in [&dev, &dev.clone(), &unbound, &DeviceInfo::new()] {
+            summary.add(dev_info);
+        }
+        assert_eq!(summary.total, 4);
+        assert_eq!(summary.by_driver["nvme"], 2);
+        assert_eq!(summary.by_driver["pciex1022,1483"], 1);
+        assert_eq!(summary.no_driver, 1);
+    }
+}
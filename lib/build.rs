@@ -5,5 +5,10 @@
 // Copyright 2022 Oxide Computer Company
 
 fn main() {
-    println!("cargo:rustc-link-lib=devinfo");
+    // Other platforms get stub implementations and have nothing to link.
+    let os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    if os == "illumos" || os == "solaris" {
+        println!("cargo:rustc-link-lib=devinfo");
+        println!("cargo:rustc-link-lib=devid");
+    }
 }
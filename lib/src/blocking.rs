@@ -0,0 +1,32 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Copyright 2022 Oxide Computer Company
+
+use std::collections::BTreeMap;
+
+use crate::{DevInfoError, DeviceInfo, DeviceKey};
+
+/// The same as [`get_devices`](crate::get_devices), named to make clear
+/// that it blocks the calling thread for the whole walk, which can take a
+/// noticeable time when prom properties are fetched.
+pub fn get_devices_blocking(
+    fetch_prom: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>, DevInfoError> {
+    crate::get_devices(fetch_prom)
+}
+
+/// Run [`get_devices_blocking`] on tokio's blocking thread pool so that the
+/// walk doesn't stall the runtime. The work itself is still synchronous.
+#[cfg(feature = "tokio")]
+pub async fn get_devices_async(
+    fetch_prom: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>, DevInfoError> {
+    match tokio::task::spawn_blocking(move || get_devices_blocking(fetch_prom))
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
@@ -0,0 +1,102 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Copyright 2022 Oxide Computer Company
+
+//! A more readable serde representation of [`DiPropValue`], for use with
+//! `#[serde(with = "devinfo::compact")]`. Most integer properties hold a
+//! single value, and this writes those as `{"Int": 5}` rather than
+//! `{"Ints": [5]}`. The tag still records the type, so values read back
+//! exactly as they were; the default representation is also accepted.
+//! Use [`compact::map`](map) for a map of values, such as
+//! [`DeviceInfo::props`](crate::DeviceInfo::props).
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::DiPropValue;
+
+#[derive(Serialize)]
+enum CompactRef<'a> {
+    Boolean(bool),
+    Int(i32),
+    Ints(&'a [i32]),
+    Int64(i64),
+    Int64s(&'a [i64]),
+    Strings(&'a [String]),
+    Bytes(&'a [u8]),
+}
+
+#[derive(Deserialize)]
+enum Compact {
+    Boolean(bool),
+    Int(i32),
+    Ints(Vec<i32>),
+    Int64(i64),
+    Int64s(Vec<i64>),
+    Strings(Vec<String>),
+    Bytes(Vec<u8>),
+}
+
+impl<'a> From<&'a DiPropValue> for CompactRef<'a> {
+    fn from(value: &'a DiPropValue) -> Self {
+        match value {
+            DiPropValue::Boolean(x) => CompactRef::Boolean(*x),
+            DiPropValue::Ints(x) if x.len() == 1 => CompactRef::Int(x[0]),
+            DiPropValue::Ints(x) => CompactRef::Ints(x),
+            DiPropValue::Int64s(x) if x.len() == 1 => CompactRef::Int64(x[0]),
+            DiPropValue::Int64s(x) => CompactRef::Int64s(x),
+            DiPropValue::Strings(x) => CompactRef::Strings(x),
+            DiPropValue::Bytes(x) => CompactRef::Bytes(x),
+        }
+    }
+}
+
+impl From<Compact> for DiPropValue {
+    fn from(value: Compact) -> Self {
+        match value {
+            Compact::Boolean(x) => DiPropValue::Boolean(x),
+            Compact::Int(x) => DiPropValue::Ints(vec![x]),
+            Compact::Ints(x) => DiPropValue::Ints(x),
+            Compact::Int64(x) => DiPropValue::Int64s(vec![x]),
+            Compact::Int64s(x) => DiPropValue::Int64s(x),
+            Compact::Strings(x) => DiPropValue::Strings(x),
+            Compact::Bytes(x) => DiPropValue::Bytes(x),
+        }
+    }
+}
+
+pub fn serialize<S: Serializer>(
+    value: &DiPropValue,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    CompactRef::from(value).serialize(s)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<DiPropValue, D::Error> {
+    Compact::deserialize(d).map(DiPropValue::from)
+}
+
+/// The compact representation for a map of property values, for use with
+/// `#[serde(with = "devinfo::compact::map")]`.
+pub mod map {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        props: &BTreeMap<String, DiPropValue>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        s.collect_map(props.iter().map(|(k, v)| (k, CompactRef::from(v))))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<BTreeMap<String, DiPropValue>, D::Error> {
+        let props = BTreeMap::<String, Compact>::deserialize(d)?;
+        Ok(props.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
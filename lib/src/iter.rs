@@ -0,0 +1,52 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Copyright 2022 Oxide Computer Company
+
+use std::collections::btree_map;
+use std::collections::BTreeMap;
+
+use crate::{DeviceInfo, DeviceKey, DiPropValue, PropPredicate};
+
+/// An iterator over the devices of a device map whose properties match a
+/// set of filters. Created by [`DeviceMapExt::filter_by_prop`].
+pub struct DeviceIter<'a> {
+    inner: btree_map::Iter<'a, DeviceKey, DeviceInfo>,
+    filters: Vec<PropPredicate>,
+}
+
+impl<'a> DeviceIter<'a> {
+    /// Further restrict the iterator to devices whose property `name` is
+    /// exactly `value`.
+    pub fn filter_by_prop(mut self, name: &str, value: DiPropValue) -> Self {
+        self.filters.push(PropPredicate::new(name, value));
+        self
+    }
+}
+
+impl<'a> Iterator for DeviceIter<'a> {
+    type Item = (&'a DeviceKey, &'a DeviceInfo);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let filters = &self.filters;
+        self.inner.find(|(_, info)| info.matches(filters))
+    }
+}
+
+/// Filtering helpers for the device maps returned by
+/// [`get_devices`](crate::get_devices) and friends.
+pub trait DeviceMapExt {
+    /// Iterate over the devices whose property `name` is exactly `value`.
+    fn filter_by_prop(&self, name: &str, value: DiPropValue) -> DeviceIter<'_>;
+}
+
+impl DeviceMapExt for BTreeMap<DeviceKey, DeviceInfo> {
+    fn filter_by_prop(&self, name: &str, value: DiPropValue) -> DeviceIter<'_> {
+        DeviceIter {
+            inner: self.iter(),
+            filters: Vec::new(),
+        }
+        .filter_by_prop(name, value)
+    }
+}
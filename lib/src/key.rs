@@ -0,0 +1,128 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Copyright 2022 Oxide Computer Company
+
+use std::cmp::Ordering;
+
+/// Identifies a device in the maps returned by [`get_devices`] and friends.
+/// Sibling nodes often share a name, e.g. `pci@0` and `pci@1`, so the unit
+/// address is part of the key and keys print as `name@address`.
+///
+/// Keys order by node name and then by unit address, comparing the
+/// comma-separated components of the address numerically, so that `pci@2`
+/// sorts before `pci@10`.
+///
+/// [`get_devices`]: crate::get_devices
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceKey {
+    pub node_name: String,
+    pub unit_address: Option<String>,
+}
+
+impl std::fmt::Display for DeviceKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.unit_address {
+            Some(a) => write!(f, "{}@{}", self.node_name, a),
+            None => write!(f, "{}", self.node_name),
+        }
+    }
+}
+
+impl Ord for DeviceKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.node_name.cmp(&other.node_name).then_with(|| {
+            match (&self.unit_address, &other.unit_address) {
+                (Some(a), Some(b)) => cmp_address(a, b),
+                (a, b) => a.cmp(b),
+            }
+        })
+    }
+}
+
+impl PartialOrd for DeviceKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compare unit addresses component by component. Unit addresses are
+/// conventionally hex, so components made up of hex digits compare by
+/// value, and sort before any that aren't. Addresses that are equal by
+/// value, such as `01` and `1`, fall back to comparing as strings so that
+/// the ordering stays consistent with equality.
+fn cmp_address(a: &str, b: &str) -> Ordering {
+    fn is_hex(s: &str) -> bool {
+        !s.is_empty() && s.bytes().all(|c| c.is_ascii_hexdigit())
+    }
+
+    let mut x = a.split(',');
+    let mut y = b.split(',');
+    loop {
+        let ord = match (x.next(), y.next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => match (is_hex(x), is_hex(y)) {
+                (true, true) => {
+                    let x = x.trim_start_matches('0').to_ascii_lowercase();
+                    let y = y.trim_start_matches('0').to_ascii_lowercase();
+                    x.len().cmp(&y.len()).then_with(|| x.cmp(&y))
+                }
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                (false, false) => x.cmp(y),
+            },
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+}
+
+// Keys are serialized in their `name@address` form so that device maps can
+// be represented as string-keyed objects in formats such as JSON.
+#[cfg(feature = "serde")]
+impl serde::Serialize for DeviceKey {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DeviceKey {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(DeviceKey::parse(&s))
+    }
+}
+
+impl DeviceKey {
+    /// Build a key from a devfs leaf such as `cpu@0` or `pci8086,1@1f,2`.
+    /// The leaf is split on its last `@`; a leaf without one, or with
+    /// nothing after it, has no unit address. If a full devfs path is given
+    /// only its final component is used.
+    pub fn parse(leaf: &str) -> DeviceKey {
+        let leaf = leaf.rsplit('/').next().unwrap_or(leaf);
+        match leaf.rsplit_once('@') {
+            Some((name, addr)) if !addr.is_empty() => DeviceKey {
+                node_name: name.to_owned(),
+                unit_address: Some(addr.to_owned()),
+            },
+            Some((name, _)) => DeviceKey {
+                node_name: name.to_owned(),
+                unit_address: None,
+            },
+            None => DeviceKey {
+                node_name: leaf.to_owned(),
+                unit_address: None,
+            },
+        }
+    }
+}
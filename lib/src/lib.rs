@@ -4,16 +4,128 @@
 
 // Copyright 2022 Oxide Computer Company
 
+mod blocking;
+#[cfg(feature = "serde")]
+pub mod compact;
+mod iter;
+mod key;
+mod prom;
+mod query;
+#[cfg(feature = "serde")]
+mod saved;
+mod summary;
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+mod sys;
+#[cfg(not(any(target_os = "illumos", target_os = "solaris")))]
+#[path = "unsupported.rs"]
 mod sys;
 
 use std::collections::BTreeMap;
-use std::fmt::{Display, Error, Formatter};
+use std::convert::{TryFrom, TryInto};
+use std::ffi::CStr;
+use std::fmt::{Display, Error, Formatter, LowerHex, UpperHex};
+use std::mem::discriminant;
 
 use num_enum::TryFromPrimitive;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-pub use crate::sys::get_devices;
+#[cfg(feature = "tokio")]
+pub use crate::blocking::get_devices_async;
+pub use crate::blocking::get_devices_blocking;
+pub use crate::iter::{DeviceIter, DeviceMapExt};
+pub use crate::key::DeviceKey;
+pub use crate::prom::{parse_idprom, DiPromValue, IdProm};
+pub use crate::query::DeviceQuery;
+#[cfg(feature = "serde")]
+pub use crate::saved::{load_devices, save_devices};
+pub use crate::summary::{summarize, TreeSummary};
+#[cfg(feature = "parallel")]
+pub use crate::sys::get_devices_parallel;
+#[cfg(feature = "private-data")]
+pub use crate::sys::raw_private_data;
+pub use crate::sys::{
+    can_fetch_prom, count_devices, for_each_device, for_each_device_ref,
+    get_both, get_device, get_device_by_instance, get_device_tree, get_devices,
+    get_devices_at, get_devices_by_driver, get_devices_with,
+    get_devices_with_paths, get_driver_props, get_prop_from, prom_available,
+    walk_minors, PromTree, PropsRef,
+};
 
-#[derive(TryFromPrimitive)]
+/// Errors returned by this crate.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DevInfoError {
+    /// An underlying libdevinfo call failed with the given OS error.
+    Io(std::io::Error),
+    /// A libdevinfo accessor returned null where a value was required.
+    NullPointer(&'static str),
+    /// A name passed to libdevinfo contained an interior nul byte.
+    InteriorNul(std::ffi::NulError),
+    /// libdevinfo is not available on this platform.
+    Unsupported,
+    /// Taking a device snapshot requires more privilege than the caller
+    /// has, e.g. because multipath information was requested, or prom
+    /// properties were requested but can't be read. See
+    /// [`can_fetch_prom`].
+    PermissionDenied,
+    /// There was not enough memory to take a device snapshot.
+    OutOfMemory,
+    /// There is no device at the given physical path.
+    NotFound(String),
+    /// A property value could not be converted to the named type, because
+    /// it is of another type or does not hold exactly one value.
+    WrongType(&'static str),
+}
+
+impl Display for DevInfoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::NullPointer(func) => write!(f, "{} returned null", func),
+            Self::InteriorNul(e) => write!(f, "{}", e),
+            Self::Unsupported => {
+                write!(f, "libdevinfo is not available on this platform")
+            }
+            Self::PermissionDenied => write!(
+                f,
+                "permission denied taking device snapshot (try running as root)"
+            ),
+            Self::OutOfMemory => {
+                write!(f, "out of memory taking device snapshot")
+            }
+            Self::NotFound(path) => write!(f, "no device at {}", path),
+            Self::WrongType(t) => {
+                write!(f, "property value is not a single {}", t)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DevInfoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::InteriorNul(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for DevInfoError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<std::ffi::NulError> for DevInfoError {
+    fn from(e: std::ffi::NulError) -> Self {
+        Self::InteriorNul(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(i32)]
 pub enum DiPropType {
     Boolean,
@@ -25,52 +137,1129 @@ pub enum DiPropType {
     Int64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum DiPropValue {
     Boolean(bool),
     Ints(Vec<i32>),
     Int64s(Vec<i64>),
     Strings(Vec<String>),
+    Bytes(Vec<u8>),
 }
 
 impl DiPropValue {
     pub fn matches_int(&self, x: i32) -> bool {
+        self.as_scalar_int() == Some(x)
+    }
+
+    /// The value of a scalar integer property, i.e. one holding exactly one
+    /// `int`. Identification properties such as `vendor-id`, `device-id`
+    /// and `class-code` are scalars, while properties such as `reg` and
+    /// `assigned-addresses` are vectors of cells and yield `None` here;
+    /// match on [`DiPropValue::Ints`] to read those.
+    pub fn as_scalar_int(&self) -> Option<i32> {
         match self {
-            Self::Ints(xs) => {
-                if xs.len() != 1 {
-                    return false;
-                }
-                xs[0] == x
-            }
-            _ => false,
+            Self::Ints(xs) if xs.len() == 1 => Some(xs[0]),
+            _ => None,
+        }
+    }
+
+    /// Decode a byte property of exactly four bytes as a big-endian
+    /// integer, the byte order of PROM data.
+    pub fn as_u32_be(&self) -> Option<u32> {
+        self.as_u32_array_be().and_then(single_u32)
+    }
+
+    /// Decode a byte property of exactly four bytes as a little-endian
+    /// integer, as some drivers publish.
+    pub fn as_u32_le(&self) -> Option<u32> {
+        self.as_u32_array_le().and_then(single_u32)
+    }
+
+    /// Decode a byte property as an array of big-endian integers. Its
+    /// length must be a multiple of four.
+    pub fn as_u32_array_be(&self) -> Option<Vec<u32>> {
+        self.u32_array(u32::from_be_bytes)
+    }
+
+    /// Decode a byte property as an array of little-endian integers. Its
+    /// length must be a multiple of four.
+    pub fn as_u32_array_le(&self) -> Option<Vec<u32>> {
+        self.u32_array(u32::from_le_bytes)
+    }
+
+    fn u32_array(&self, decode: fn([u8; 4]) -> u32) -> Option<Vec<u32>> {
+        match self {
+            Self::Bytes(xs) if xs.len() % 4 == 0 => Some(
+                xs.chunks_exact(4)
+                    .map(|x| decode([x[0], x[1], x[2], x[3]]))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Render the value with integers in decimal, as the `Display`
+    /// implementation does.
+    #[deprecated(note = "use `format(Radix::Decimal)` or `to_string()`")]
+    pub fn display_decimal(&self) -> String {
+        self.format(Radix::Decimal)
+    }
+
+    /// Render the value with integers in the given radix. `Radix::Decimal`
+    /// matches the `Display` implementation and `Radix::Hex` the `LowerHex`
+    /// one.
+    pub fn format(&self, radix: Radix) -> String {
+        match radix {
+            Radix::Hex => format!("{:x}", self),
+            Radix::Decimal => self.to_string(),
         }
     }
 }
 
+fn single_u32(xs: Vec<u32>) -> Option<u32> {
+    match xs[..] {
+        [x] => Some(x),
+        _ => None,
+    }
+}
+
+/// Take the only element of a property value's array.
+fn single<T: Clone>(xs: &[T], t: &'static str) -> Result<T, DevInfoError> {
+    match xs {
+        [x] => Ok(x.clone()),
+        _ => Err(DevInfoError::WrongType(t)),
+    }
+}
+
+impl TryFrom<&DiPropValue> for i32 {
+    type Error = DevInfoError;
+
+    fn try_from(value: &DiPropValue) -> Result<Self, Self::Error> {
+        match value {
+            DiPropValue::Ints(xs) => single(xs, "i32"),
+            _ => Err(DevInfoError::WrongType("i32")),
+        }
+    }
+}
+
+/// A single 32-bit integer is widened, as some drivers use `int` and
+/// `int64` properties interchangeably.
+impl TryFrom<&DiPropValue> for i64 {
+    type Error = DevInfoError;
+
+    fn try_from(value: &DiPropValue) -> Result<Self, Self::Error> {
+        match value {
+            DiPropValue::Int64s(xs) => single(xs, "i64"),
+            DiPropValue::Ints(xs) => single(xs, "i64").map(i64::from),
+            _ => Err(DevInfoError::WrongType("i64")),
+        }
+    }
+}
+
+impl TryFrom<&DiPropValue> for String {
+    type Error = DevInfoError;
+
+    fn try_from(value: &DiPropValue) -> Result<Self, Self::Error> {
+        match value {
+            DiPropValue::Strings(xs) => single(xs, "string"),
+            _ => Err(DevInfoError::WrongType("string")),
+        }
+    }
+}
+
+impl TryFrom<&DiPropValue> for bool {
+    type Error = DevInfoError;
+
+    fn try_from(value: &DiPropValue) -> Result<Self, Self::Error> {
+        match value {
+            DiPropValue::Boolean(x) => Ok(*x),
+            _ => Err(DevInfoError::WrongType("bool")),
+        }
+    }
+}
+
+/// A property value borrowed from a device snapshot, as passed to the
+/// callback of [`for_each_device_ref`]. Reading one allocates nothing; use
+/// [`DiPropValueRef::to_value`] for a value that outlives the callback.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiPropValueRef<'a> {
+    Boolean(bool),
+    Ints(&'a [i32]),
+    Int64s(&'a [i64]),
+    Strings(PropStrings<'a>),
+    Bytes(&'a [u8]),
+}
+
+impl<'a> DiPropValueRef<'a> {
+    /// The first string of a string value, borrowed from the snapshot, for
+    /// comparisons that shouldn't allocate. Other types yield `None`.
+    pub fn as_cstr(&self) -> Option<&'a CStr> {
+        match self {
+            Self::Strings(x) => x.iter().next(),
+            _ => None,
+        }
+    }
+
+    /// Copy the value out of the snapshot.
+    pub fn to_value(&self) -> DiPropValue {
+        match *self {
+            Self::Boolean(x) => DiPropValue::Boolean(x),
+            Self::Ints(x) => DiPropValue::Ints(x.to_vec()),
+            Self::Int64s(x) => DiPropValue::Int64s(x.to_vec()),
+            Self::Strings(x) => DiPropValue::Strings(
+                x.iter().map(|s| s.to_string_lossy().into_owned()).collect(),
+            ),
+            Self::Bytes(x) => DiPropValue::Bytes(x.to_vec()),
+        }
+    }
+}
+
+/// The strings of a string property, borrowed from a device snapshot. They
+/// are stored back to back, each nul-terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PropStrings<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> PropStrings<'a> {
+    /// `data` must be empty or end in a nul.
+    #[cfg_attr(
+        not(any(target_os = "illumos", target_os = "solaris")),
+        allow(dead_code)
+    )]
+    pub(crate) fn new(data: &'a [u8]) -> PropStrings<'a> {
+        debug_assert!(matches!(data.last(), None | Some(0)));
+        PropStrings { data }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'a CStr> + 'a {
+        self.data.split_inclusive(|b| *b == 0).map(|s| {
+            CStr::from_bytes_with_nul(s).expect("strings are nul-terminated")
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.iter().filter(|b| **b == 0).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// The radix integer property values are rendered in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    Hex,
+    Decimal,
+}
+
+impl Radix {
+    /// The radix to render the property `name` in. By IEEE 1275 convention
+    /// `#`-prefixed properties are counts, which read better in decimal
+    /// whatever radix was asked for.
+    pub fn for_prop(self, name: &str) -> Radix {
+        if name.starts_with('#') {
+            Radix::Decimal
+        } else {
+            self
+        }
+    }
+}
+
+/// Integers are rendered in decimal; use `{:x}` or `{:X}` for hex.
 impl Display for DiPropValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Self::Boolean(x) => write!(f, "{}", x),
+            Self::Ints(x) => write!(f, "{:?}", x),
+            Self::Int64s(x) => write!(f, "{:?}", x),
+            Self::Strings(x) => write!(f, "{:?}", x),
+            Self::Bytes(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
+impl LowerHex for DiPropValue {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         match self {
             Self::Boolean(x) => write!(f, "{}", x),
             Self::Ints(x) => write!(f, "{:x?}", x),
             Self::Int64s(x) => write!(f, "{:x?}", x),
             Self::Strings(x) => write!(f, "{:?}", x),
+            Self::Bytes(x) => write!(f, "{:02x?}", x),
         }
     }
 }
 
-#[derive(Debug)]
+impl UpperHex for DiPropValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Self::Boolean(x) => write!(f, "{}", x),
+            Self::Ints(x) => write!(f, "{:X?}", x),
+            Self::Int64s(x) => write!(f, "{:X?}", x),
+            Self::Strings(x) => write!(f, "{:?}", x),
+            Self::Bytes(x) => write!(f, "{:02X?}", x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DeviceInfo {
+    /// The node name, as in the device's [`DeviceKey`]. Kept here too so
+    /// that a device handed around without its key can still be named.
+    pub node_name: String,
     pub props: BTreeMap<String, DiPropValue>,
+    /// Which property list each entry in `props` came from.
+    pub prop_sources: BTreeMap<String, PropSource>,
+    /// Properties hidden by a same-named property on a list with higher
+    /// precedence, e.g. a hardware property overridden by the driver.
+    pub shadowed_props: Vec<ShadowedProp>,
     pub prom_props: BTreeMap<String, Vec<u8>>,
+    /// The minor nodes this device exposes, e.g. the entries behind
+    /// `/dev/dsk` and `/dev/rdsk` for a disk.
+    pub minors: Vec<MinorNode>,
+    /// For a multipath client, the paths to the device. Only collected by
+    /// [`get_devices_with_paths`].
+    pub paths: Vec<PathInfo>,
+    /// Layered driver links to or from this device, e.g. from a file system
+    /// to the disk it is mounted from. Only collected when the snapshot is
+    /// taken with [`SnapshotFlags::LAYERING`]. Each link is listed on both
+    /// the devices it joins.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub links: Vec<DeviceLink>,
+    /// The device's hotplug connectors and ports. Only collected when the
+    /// snapshot is taken with [`SnapshotFlags::HOTPLUG`], e.g. through
+    /// [`get_devices_with`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hotplug: Vec<HotplugConnector>,
+    /// Problems encountered while reading this device's properties.
+    pub warnings: Vec<DevInfoWarning>,
+    /// The driver instance number, or `-1` if no instance is assigned.
+    pub instance: i32,
+    /// The node's position among its siblings in the snapshot, in the order
+    /// a walk visits them. Together with the key this tells apart sibling
+    /// nodes that share a name and unit address, as pseudo devices
+    /// sometimes do. The root of a snapshot is at position 0.
+    pub sibling_index: usize,
+    pub node_state: NodeState,
+    /// The name the node was bound to a driver with, if any.
+    pub binding_name: Option<String>,
+    /// The name of the driver bound to the node, if any.
+    pub driver_name: Option<String>,
+    /// The major number of the bound driver, as in `/etc/name_to_major`.
+    pub driver_major: Option<i32>,
+    /// The entry points the bound driver provides. Empty if no driver is
+    /// attached.
+    #[cfg_attr(feature = "serde", serde(default = "DriverOps::empty"))]
+    pub driver_ops: DriverOps,
+    pub node_class: NodeClass,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub multipath_role: MultipathRole,
+    pub nodeid: NodeId,
+    pub attributes: NodeAttributes,
+    pub flags: NodeFlags,
+    /// The compatible names the node binds against, most specific first.
+    pub compatible: Vec<String>,
+    /// The canonical `/devices` path of this node, without the `/devices`
+    /// prefix, e.g. `/pci@0,0/pci8086,1@1`.
+    pub devfs_path: Option<String>,
+    /// The encoded device id, a stable identifier for storage devices that
+    /// survives changes to instance numbers and paths, e.g. from
+    /// recabling.
+    pub devid: Option<String>,
+    /// The physical path the snapshot containing this device was rooted
+    /// at, i.e. the path handed to `di_init`.
+    pub snapshot_root: Option<String>,
+}
+
+/// A problem encountered while reading part of a device, such as one of its
+/// properties. The device is still returned, without the part that
+/// couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DevInfoWarning {
+    /// The node being read.
+    pub node: DeviceKey,
+    /// The property being read, if the problem was with a property.
+    pub prop: Option<String>,
+    /// The OS error the failing call reported, if any.
+    pub errno: Option<i32>,
+    /// What went wrong, e.g. `failed to get ints`.
+    pub message: String,
+}
+
+impl Display for DevInfoWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}: ", self.node)?;
+        if let Some(prop) = &self.prop {
+            write!(f, "{}: ", prop)?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(errno) = self.errno {
+            let e = std::io::Error::from_raw_os_error(errno);
+            write!(f, ": {}", e)?;
+        }
+        Ok(())
+    }
+}
+
+/// How far a device node has progressed through attach, from
+/// `ddi_node_state_t`. States are ordered, so e.g. `state >=
+/// NodeState::Attached` tests for an attached driver.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, TryFromPrimitive,
+)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(i32)]
+pub enum NodeState {
+    Invalid = -1,
+    Proto = 0,
+    /// In the orphan list.
+    Linked,
+    /// In the per-driver list, i.e. bound to a driver.
+    Bound,
+    /// Bus address assigned.
+    Initialized,
+    /// Device known to exist.
+    Probed,
+    /// Driver attached.
+    Attached,
+    /// Post-attach processing complete.
+    Ready,
+}
+
+/// Whether a node came from the firmware (PROM) device tree or was created
+/// by software, from `ddi_node_class_t`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(i32)]
+pub enum NodeClass {
+    Prom = 0,
+    Pseudo,
+}
+
+/// A node's part in multipathing (MPxIO), from its `MDI_COMPONENT_*` value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(u32)]
+pub enum MultipathRole {
+    /// Not involved in multipathing.
+    #[default]
+    None = 0,
+    /// The virtual host controller interface, e.g. `scsi_vhci`.
+    Vhci = 0x1,
+    /// A physical host controller that provides paths.
+    Phci = 0x2,
+    /// A multipathed client device, e.g. a disk.
+    Client = 0x4,
+}
+
+/// Where a node's id comes from, as reported by `di_nodeid`. Only PROM
+/// nodes were enumerated by firmware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NodeId {
+    /// The node id was assigned by the PROM. This is the same id that
+    /// [`PromTree`] navigates by, so the node can be found there.
+    Prom(i32),
+    /// A pseudo device with no node id.
+    Pseudo,
+    /// A node id assigned by the system that persists across reboots.
+    Persistent,
+}
+
+bitflags::bitflags! {
+    /// Attributes of a device node, from `devi_node_attributes`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct NodeAttributes: u32 {
+        /// The node persists even when no driver is attached, e.g. a node
+        /// from the PROM tree or `driver.conf` rather than a hotplugged one.
+        const PERSISTENT = 0x01;
+        /// The node id was assigned by the system rather than the PROM.
+        const AUTO_ASSIGNED_NODEID = 0x02;
+        /// The node is a multipath virtual host controller (vHCI).
+        const VHCI_NODE = 0x04;
+        /// The node is hidden from `/devices`.
+        const HIDDEN_NODE = 0x08;
+
+        const _ = !0;
+    }
+}
+
+bitflags::bitflags! {
+    /// The kinds of entry points the driver bound to a node provides, from
+    /// `di_driver_ops`. Nexus drivers have bus ops; leaf drivers have
+    /// character/block or STREAMS ops.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct DriverOps: u32 {
+        /// Bus ops, i.e. the driver is a nexus (`DI_BUS_OPS`).
+        const BUS = 0x1;
+        /// Character/block device ops (`DI_CB_OPS`).
+        const CB = 0x2;
+        /// STREAMS ops (`DI_STREAM_OPS`).
+        const STREAM = 0x4;
+
+        const _ = !0;
+    }
+}
+
+bitflags::bitflags! {
+    /// Framework state flags of a device node, from `devi_flags`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct NodeFlags: u32 {
+        const BUSY = 0x0001;
+        /// Children have been created from their specifications.
+        const MADE_CHILDREN = 0x0002;
+        /// All existing children are attached.
+        const ATTACHED_CHILDREN = 0x0004;
+        /// The branch rooted at this node is held.
+        const BRANCH_HELD = 0x0008;
+        /// Driver binding is prevented.
+        const NO_BIND = 0x0010;
+        /// The device id is in the devid cache.
+        const CACHED_DEVID = 0x0020;
+        const PHCI_SIGNALS_VHCI = 0x0040;
+        /// The driver is rebound after child initialization.
+        const REBIND = 0x0080;
+        /// The device has been retired by FMA.
+        const RETIRED = 0x0100;
+        /// The device is being evaluated for retirement.
+        const RETIRING = 0x0200;
+        /// The node is a PCI device.
+        const PCI_DEVICE = 0x2000;
+
+        const _ = !0;
+    }
+}
+
+bitflags::bitflags! {
+    /// How much detail a device snapshot includes, from the `DINFO*` flags
+    /// to `di_init`. With no flags set only the root node itself is copied
+    /// (`DINFOCPYONE`).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct SnapshotFlags: u32 {
+        /// Include the nodes below the root.
+        const SUBTREE = 0x01;
+        /// Include minor node data.
+        const MINOR = 0x02;
+        /// Include properties.
+        const PROP = 0x04;
+        /// Include multipath (MPxIO) information.
+        const PATH = 0x08;
+        /// Load and attach all drivers before taking the snapshot. This is
+        /// expensive.
+        const FORCE = 0x20;
+        /// Include layered driver usage, i.e. which devices are opened by
+        /// which others (`DINFOLYR`).
+        const LAYERING = 0x40;
+        /// Include hotplug connectors and ports.
+        const HOTPLUG = 0x400000;
+        /// The subtree with properties and minor data (`DINFOCPYALL`).
+        const CPYALL =
+            Self::SUBTREE.bits() | Self::MINOR.bits() | Self::PROP.bits();
+    }
+}
+
+/// Defaults to [`SnapshotFlags::CPYALL`], which is what [`get_devices`]
+/// uses.
+impl Default for SnapshotFlags {
+    fn default() -> Self {
+        Self::CPYALL
+    }
+}
+
+/// The property list a property was found on, in the order of precedence
+/// the DDI gives them when looking a property up by name.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, TryFromPrimitive,
+)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(i32)]
+pub enum PropSource {
+    /// Properties created by the driver.
+    Driver = 0,
+    /// Properties created by the system, e.g. from `driver.conf`.
+    System,
+    /// Properties global to all instances of a driver.
+    Global,
+    /// Properties created by the parent nexus or from the firmware.
+    Hardware,
+}
+
+/// A property that lost out to a same-named property on a list with higher
+/// precedence.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ShadowedProp {
+    pub name: String,
+    pub source: Option<PropSource>,
+    pub value: DiPropValue,
+}
+
+/// The state of a multipath path, from `di_path_state_t`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(i32)]
+pub enum PathState {
+    Unknown = 0,
+    Offline,
+    Standby,
+    Online,
+    Fault,
+}
+
+/// One end of a [`DeviceLink`], from a `di_lnode`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LinkEnd {
+    /// The name of the module at this end, e.g. `zfs` or `sd`.
+    pub name: String,
+    /// The device at this end. Consumers that aren't devices, such as file
+    /// systems, have none.
+    pub node: Option<DeviceKey>,
+}
+
+/// A layered driver link: the source has the target device open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceLink {
+    pub source: LinkEnd,
+    pub target: LinkEnd,
+    /// Whether the target was opened as a block or character device.
+    pub spec_type: SpecType,
+}
+
+/// The state of a hotplug connector or port, from `ddi_hp_cn_state_t`.
+/// States are ordered, so e.g. `state >= HotplugState::Powered` tests for a
+/// powered connector.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, TryFromPrimitive,
+)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(i32)]
+pub enum HotplugState {
+    Unknown = 0,
+    /// Nothing is plugged into the connector.
+    Empty = 0x1000,
+    /// A device is present but not powered.
+    Present = 0x2000,
+    Powered = 0x3000,
+    Enabled = 0x4000,
+    /// A port with no device node.
+    PortEmpty = 0x5000,
+    /// A port with a device node, whose driver is not attached.
+    PortPresent = 0x6000,
+    Offline = 0x7000,
+    /// A port whose device node has its driver attached.
+    Attached = 0x8000,
+    Maintenance = 0x9000,
+}
+
+/// A hotplug connector or port of a device, e.g. a PCIe slot. Only
+/// collected when [`SnapshotFlags::HOTPLUG`] is given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HotplugConnector {
+    /// The connector name, e.g. `pcie0`.
+    pub name: String,
+    pub state: HotplugState,
+}
+
+/// One path to a multipath client device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PathInfo {
+    /// The address of the client on the path, e.g. a SAS WWN.
+    pub bus_address: Option<String>,
+    pub state: PathState,
+    /// The devfs path of the controller (pHCI) the path goes through.
+    pub phci: Option<String>,
+}
+
+/// The raw private data of a node, as read by [`raw_private_data`]. Either
+/// half is `None` if the snapshot holds no such data for the node.
+#[cfg(feature = "private-data")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivateData {
+    /// Data kept about the node by its parent nexus driver.
+    pub parent: Option<Vec<u8>>,
+    /// Data kept by the node's own driver.
+    pub driver: Option<Vec<u8>>,
+}
+
+/// Whether a minor node is a block or character device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SpecType {
+    Block,
+    Char,
+}
+
+/// A minor node of a device, i.e. something that can appear under `/dev`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MinorNode {
+    /// The minor name, e.g. `a` or `a,raw` for a disk slice.
+    pub name: String,
+    /// The node type, e.g. `ddi_block:channel`.
+    pub node_type: Option<String>,
+    pub spec_type: SpecType,
+    /// The minor's `/devices` path, without the `/devices` prefix, e.g.
+    /// `/pci@0,0/pci8086,1@1/disk@0,0:a`. This is what the `/dev` links for
+    /// the minor point at.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub minor_path: Option<String>,
+}
+
+impl MinorNode {
+    /// Whether the node type is `prefix` or lies below it in the
+    /// colon-separated type hierarchy, e.g. `ddi_block` matches
+    /// `ddi_block:cdrom`. Unlike a plain string prefix, `ddi_block` does
+    /// not match `ddi_blockdev`.
+    pub fn is_type(&self, prefix: &str) -> bool {
+        match self
+            .node_type
+            .as_deref()
+            .and_then(|t| t.strip_prefix(prefix))
+        {
+            Some(rest) => rest.is_empty() || rest.starts_with(':'),
+            None => false,
+        }
+    }
+}
+
+/// The role a PCI function plays in an SR-IOV configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SriovRole {
+    /// A physical function, i.e. a real device that may host VFs.
+    Physical,
+    /// A virtual function carved out of a physical function.
+    Virtual,
+}
+
+/// A requirement that a device's property `name` be exactly `value`, for use
+/// with [`DeviceInfo::matches`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropPredicate {
+    pub name: String,
+    pub value: DiPropValue,
+}
+
+impl PropPredicate {
+    pub fn new(name: &str, value: DiPropValue) -> Self {
+        Self {
+            name: name.to_owned(),
+            value,
+        }
+    }
+}
+
+/// A difference between a device's properties and an expected property set,
+/// as reported by [`DeviceInfo::matches_expected`].
+#[derive(Debug, PartialEq)]
+pub enum Mismatch<'a> {
+    /// The device does not have the property at all.
+    Missing { name: &'a str },
+    /// The property exists but holds a different kind of value.
+    TypeDiffers {
+        name: &'a str,
+        expected: &'a DiPropValue,
+        actual: &'a DiPropValue,
+    },
+    /// The property has the expected type but a different value.
+    ValueDiffers {
+        name: &'a str,
+        expected: &'a DiPropValue,
+        actual: &'a DiPropValue,
+    },
+}
+
+/// How a device's properties changed between two reads of it, as reported
+/// by [`DeviceInfo::diff`].
+#[derive(Debug, Default, PartialEq)]
+pub struct PropDiff<'a> {
+    /// Properties that appeared, with their values.
+    pub added: BTreeMap<&'a str, &'a DiPropValue>,
+    /// Properties that vanished, with the values they had.
+    pub removed: BTreeMap<&'a str, &'a DiPropValue>,
+    /// Properties whose value changed, as `(old, new)`.
+    pub changed: BTreeMap<&'a str, (&'a DiPropValue, &'a DiPropValue)>,
+}
+
+impl PropDiff<'_> {
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+/// A coarse category for a device, as inferred by
+/// [`DeviceInfo::device_class`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DeviceClass {
+    Cpu,
+    Memory,
+    Storage,
+    Network,
+    Bus,
+    Pseudo,
+    Other,
 }
 
 impl DeviceInfo {
     pub fn new() -> DeviceInfo {
         DeviceInfo {
+            node_name: String::new(),
             props: BTreeMap::new(),
+            prop_sources: BTreeMap::new(),
+            shadowed_props: Vec::new(),
             prom_props: BTreeMap::new(),
+            minors: Vec::new(),
+            paths: Vec::new(),
+            links: Vec::new(),
+            hotplug: Vec::new(),
+            warnings: Vec::new(),
+            instance: -1,
+            sibling_index: 0,
+            node_state: NodeState::Invalid,
+            binding_name: None,
+            driver_name: None,
+            driver_major: None,
+            node_class: NodeClass::Pseudo,
+            multipath_role: MultipathRole::None,
+            nodeid: NodeId::Pseudo,
+            driver_ops: DriverOps::empty(),
+            attributes: NodeAttributes::empty(),
+            flags: NodeFlags::empty(),
+            compatible: Vec::new(),
+            devfs_path: None,
+            devid: None,
+            snapshot_root: None,
         }
     }
+
+    /// Determine whether this device is an SR-IOV physical or virtual
+    /// function. A `vf` property marks a virtual function, while a `pf` or
+    /// `iov-busnum` property marks a physical function. Devices carrying
+    /// none of these are not participating in SR-IOV and yield `None`.
+    pub fn sriov_role(&self) -> Option<SriovRole> {
+        if self.props.contains_key("vf") {
+            return Some(SriovRole::Virtual);
+        }
+        if self.props.contains_key("pf")
+            || self.props.contains_key("iov-busnum")
+        {
+            return Some(SriovRole::Physical);
+        }
+        None
+    }
+
+    /// Render the device's properties one per line, as `Display` does, with
+    /// integers in `radix`. `#`-prefixed count properties are always in
+    /// decimal; see [`Radix::for_prop`].
+    pub fn display(&self, radix: Radix) -> DisplayProps<'_> {
+        DisplayProps { info: self, radix }
+    }
+
+    /// A name to show people for this device: the `model` property if it
+    /// has one, else its most specific compatible name, else the name it
+    /// was bound by, else `name@address` as in its `/devices` path.
+    pub fn label(&self) -> String {
+        if let Some(model) = self.prop_string("model") {
+            return model.to_owned();
+        }
+        if let Some(name) =
+            self.compatible.first().or(self.binding_name.as_ref())
+        {
+            return name.clone();
+        }
+        // The last component of the devfs path is the node's name@address.
+        match self
+            .devfs_path
+            .as_deref()
+            .and_then(|p| p.rsplit('/').next())
+        {
+            Some(leaf) if !leaf.is_empty() => leaf.to_owned(),
+            _ => self.node_name.clone(),
+        }
+    }
+
+    /// Make a best-effort guess at what kind of device this is. The
+    /// `device_type` property is consulted first, then the PCI base class
+    /// from `class-code`, then the node name, and finally whether the node
+    /// is a pseudo device.
+    pub fn device_class(&self) -> DeviceClass {
+        if let Some(t) = self.prop_string("device_type") {
+            match t {
+                "cpu" => return DeviceClass::Cpu,
+                "memory" => return DeviceClass::Memory,
+                "network" => return DeviceClass::Network,
+                "block" | "disk" | "ide" | "sata" | "scsi" | "scsi-2" => {
+                    return DeviceClass::Storage
+                }
+                "pci" | "pciex" | "isa" | "lpc" | "usb" | "i2c" => {
+                    return DeviceClass::Bus
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(code) = self.prop_int("class-code") {
+            match (code >> 16) & 0xff {
+                0x01 => return DeviceClass::Storage,
+                0x02 => return DeviceClass::Network,
+                0x05 => return DeviceClass::Memory,
+                0x06 | 0x0c => return DeviceClass::Bus,
+                0x0b => return DeviceClass::Cpu,
+                _ => {}
+            }
+        }
+
+        match self.node_name.as_str() {
+            "cpu" => DeviceClass::Cpu,
+            "memory" => DeviceClass::Memory,
+            _ if self.node_class == NodeClass::Pseudo => DeviceClass::Pseudo,
+            _ => DeviceClass::Other,
+        }
+    }
+
+    /// The physical slot number this device sits in. This is taken from
+    /// the `physical-slot#` property that PCI Express nexus drivers publish
+    /// for slot-implemented ports, or failing that from the number that
+    /// ends the slot's name in `slot-names` (e.g. `Slot3`), and finally
+    /// from the name of the device's hotplug connector (e.g. `pcie3`).
+    pub fn physical_slot(&self) -> Option<u32> {
+        if let Some(x) = self
+            .prop_int("physical-slot#")
+            .or_else(|| self.prop_int("physical-slot"))
+        {
+            return u32::try_from(x).ok();
+        }
+        let names = self.slot_names();
+        let name = names
+            .iter()
+            .find(|(dev, _)| *dev == 0)
+            .or_else(|| names.first())
+            .map(|(_, name)| name.as_str())
+            .or_else(|| self.hotplug.first().map(|c| c.name.as_str()))?;
+        trailing_number(name)
+    }
+
+    /// The slot names a bridge gives its slots, decoded from `slot-names`,
+    /// as `(device number, name)` pairs. The property starts with a mask
+    /// of the device numbers that have a slot, followed by one
+    /// nul-terminated name for each bit set, lowest first.
+    pub fn slot_names(&self) -> Vec<(u32, String)> {
+        let bytes = match self.props.get("slot-names") {
+            Some(DiPropValue::Bytes(x)) => x.clone(),
+            Some(DiPropValue::Ints(x)) => {
+                x.iter().flat_map(|x| x.to_ne_bytes()).collect()
+            }
+            _ => return Vec::new(),
+        };
+        if bytes.len() < 4 {
+            return Vec::new();
+        }
+        let (mask, names) = bytes.split_at(4);
+        let mask = u32::from_ne_bytes(mask.try_into().unwrap());
+
+        let mut names = names.split(|b| *b == 0);
+        (0..32)
+            .filter(|dev| mask & (1 << dev) != 0)
+            .map_while(|dev| {
+                let name = names.next().filter(|n| !n.is_empty())?;
+                Some((dev, String::from_utf8_lossy(name).into_owned()))
+            })
+            .collect()
+    }
+
+    /// The base physical address of this device's first non-configuration
+    /// space register, decoded from `assigned-addresses` or, failing that,
+    /// `reg`. Entries are assumed to use the five-cell PCI encoding
+    /// (`phys.hi`, `phys.mid`, `phys.lo`, `size.hi`, `size.lo`), where bits
+    /// 24-25 of `phys.hi` give the address space and zero means config
+    /// space. `reg` entries are only used if bit 31 of `phys.hi`, the `n`
+    /// bit, is set: otherwise they are relocatable and hold an offset
+    /// rather than an address.
+    pub fn base_address(&self) -> Option<u64> {
+        const NON_RELOCATABLE: u32 = 0x8000_0000;
+
+        ["assigned-addresses", "reg"].iter().find_map(|name| {
+            let cells = match self.props.get(*name) {
+                Some(DiPropValue::Ints(xs)) => xs,
+                _ => return None,
+            };
+            cells
+                .chunks_exact(5)
+                .find(|entry| {
+                    let hi = entry[0] as u32;
+                    (hi >> 24) & 0x3 != 0
+                        && (*name != "reg" || hi & NON_RELOCATABLE != 0)
+                })
+                .map(|entry| {
+                    (u64::from(entry[1] as u32) << 32)
+                        | u64::from(entry[2] as u32)
+                })
+        })
+    }
+
+    /// Collect the version-like string properties of this device, such as
+    /// `firmware-version` or `version`, keyed by property name. A property
+    /// is considered version-like if it is named `version` or its name ends
+    /// in `-version` or `_version`. Multi-valued properties are joined with
+    /// `", "`.
+    pub fn versions(&self) -> BTreeMap<String, String> {
+        self.props
+            .iter()
+            .filter(|(name, _)| {
+                name.as_str() == "version"
+                    || name.ends_with("-version")
+                    || name.ends_with("_version")
+            })
+            .filter_map(|(name, value)| match value {
+                DiPropValue::Strings(xs) if !xs.is_empty() => {
+                    Some((name.clone(), xs.join(", ")))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Look up a prom property and decode it with [`DiPromValue::decode`].
+    /// The raw bytes remain available in `prom_props`.
+    pub fn prom_value(&self, name: &str) -> Option<DiPromValue> {
+        self.prom_props.get(name).map(|x| DiPromValue::decode(x))
+    }
+
+    /// Look up a prom property encoded as a single nul-terminated string,
+    /// such as `model`. Unlike [`DeviceInfo::prom_value`] this doesn't
+    /// guess: data that isn't a nul-terminated UTF-8 string yields `None`.
+    pub fn prom_prop_string(&self, name: &str) -> Option<String> {
+        let bytes = self.prom_props.get(name)?.strip_suffix(&[0])?;
+        if bytes.contains(&0) {
+            return None;
+        }
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+
+    /// Look up a prom property encoded as a single big-endian 32-bit
+    /// integer, such as `#address-cells`. Data of any other length yields
+    /// `None`.
+    pub fn prom_prop_u32(&self, name: &str) -> Option<u32> {
+        let bytes = self.prom_props.get(name)?;
+        let bytes: [u8; 4] = bytes.as_slice().try_into().ok()?;
+        Some(u32::from_be_bytes(bytes))
+    }
+
+    /// Check whether every predicate holds for this device, stopping at the
+    /// first one that doesn't.
+    pub fn matches(&self, predicates: &[PropPredicate]) -> bool {
+        predicates
+            .iter()
+            .all(|p| self.props.get(&p.name) == Some(&p.value))
+    }
+
+    /// Compare this device against an expected set of property values,
+    /// returning one [`Mismatch`] per expected property that is missing or
+    /// differs. An empty result means the device matches. Properties the
+    /// device has beyond the expected set are not reported.
+    pub fn matches_expected<'a>(
+        &'a self,
+        expected: &'a BTreeMap<String, DiPropValue>,
+    ) -> Vec<Mismatch<'a>> {
+        let mut result = Vec::new();
+        for (name, want) in expected {
+            match self.props.get(name) {
+                None => result.push(Mismatch::Missing { name }),
+                Some(have) if discriminant(have) != discriminant(want) => {
+                    result.push(Mismatch::TypeDiffers {
+                        name,
+                        expected: want,
+                        actual: have,
+                    })
+                }
+                Some(have) if have != want => {
+                    result.push(Mismatch::ValueDiffers {
+                        name,
+                        expected: want,
+                        actual: have,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        result
+    }
+
+    /// Compare this device's properties against a later read of the same
+    /// device, e.g. to follow properties that drivers update live.
+    pub fn diff<'a>(&'a self, later: &'a DeviceInfo) -> PropDiff<'a> {
+        let mut diff = PropDiff::default();
+        for (name, old) in &self.props {
+            match later.props.get(name) {
+                None => {
+                    diff.removed.insert(name, old);
+                }
+                Some(new) if new != old => {
+                    diff.changed.insert(name, (old, new));
+                }
+                Some(_) => {}
+            }
+        }
+        for (name, new) in &later.props {
+            if !self.props.contains_key(name) {
+                diff.added.insert(name, new);
+            }
+        }
+        diff
+    }
+
+    /// Look up a property on exactly one property list, e.g. the value the
+    /// driver set even where it is also on another list. The merged
+    /// `props` map only holds the value with the highest precedence; those
+    /// it shadows are found in `shadowed_props`.
+    pub fn prop_from(
+        &self,
+        name: &str,
+        source: PropSource,
+    ) -> Option<&DiPropValue> {
+        if self.prop_sources.get(name) == Some(&source) {
+            return self.props.get(name);
+        }
+        self.shadowed_props
+            .iter()
+            .find(|p| p.name == name && p.source == Some(source))
+            .map(|p| &p.value)
+    }
+
+    /// Look up a string property and borrow its first string. Use
+    /// [`PropsRef::get_cstr`] to compare against the snapshot itself.
+    pub fn prop_string(&self, name: &str) -> Option<&str> {
+        match self.props.get(name) {
+            Some(DiPropValue::Strings(xs)) => xs.first().map(|x| x.as_str()),
+            _ => None,
+        }
+    }
+
+    fn prop_int(&self, name: &str) -> Option<i32> {
+        self.props.get(name)?.as_scalar_int()
+    }
+}
+
+/// The number that ends a slot or connector name, e.g. 3 for `pcie3`.
+fn trailing_number(name: &str) -> Option<u32> {
+    let prefix = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    name[prefix.len()..].parse().ok()
 }
 
 impl Default for DeviceInfo {
@@ -79,5 +1268,78 @@ impl Default for DeviceInfo {
     }
 }
 
+/// Prints each property on its own line as `name: value`, without any
+/// color, so the output is suitable for logs. Integers are in decimal; use
+/// [`DeviceInfo::display`] for hex.
+impl Display for DeviceInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        self.display(Radix::Decimal).fmt(f)
+    }
+}
+
+/// Renders a device's properties as its `Display` implementation does, but
+/// with integers in a given radix. Created by [`DeviceInfo::display`].
+pub struct DisplayProps<'a> {
+    info: &'a DeviceInfo,
+    radix: Radix,
+}
+
+impl Display for DisplayProps<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        for (name, value) in &self.info.props {
+            let radix = self.radix.for_prop(name);
+            writeln!(f, "{}: {}", name, value.format(radix))?;
+        }
+        Ok(())
+    }
+}
+
+/// A device along with the devices beneath it in the tree.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceNode {
+    pub key: DeviceKey,
+    pub info: DeviceInfo,
+    pub children: Vec<DeviceNode>,
+}
+
+impl DeviceNode {
+    /// Whether any devices sit beneath this one.
+    pub fn has_children(&self) -> bool {
+        !self.children.is_empty()
+    }
+
+    /// Whether this is a leaf of the tree, e.g. a PCI endpoint rather than
+    /// a bridge.
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Every node beneath this one, depth first, with each node before its
+    /// children. The node itself is not included. Filtering with
+    /// [`DeviceNode::is_leaf`] gives e.g. the endpoints under a bridge.
+    pub fn descendants(&self) -> impl Iterator<Item = &DeviceNode> {
+        let mut stack = vec![self.children.iter()];
+        std::iter::from_fn(move || loop {
+            match stack.last_mut()?.next() {
+                Some(node) => {
+                    stack.push(node.children.iter());
+                    return Some(node);
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        })
+    }
+}
+
+/// The device hierarchy, as returned by [`get_device_tree`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceTree {
+    pub root: DeviceNode,
+}
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,135 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Copyright 2022 Oxide Computer Company
+
+use std::convert::TryInto;
+use std::fmt::{Display, Error, Formatter};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A prom property value decoded according to IEEE 1275 conventions.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DiPromValue {
+    /// One or more nul-terminated strings.
+    Strings(Vec<String>),
+    /// Big-endian 32-bit integers.
+    Ints(Vec<i32>),
+    /// Data that matches neither of the above, including empty values.
+    Bytes(Vec<u8>),
+}
+
+impl DiPromValue {
+    /// Decode raw prom property data. Prom values carry no type
+    /// information, so this is a heuristic: nul-terminated runs of printable
+    /// ASCII are taken to be strings, data whose length is a multiple of
+    /// four is taken to be integers, and anything else is left as bytes.
+    pub fn decode(bytes: &[u8]) -> DiPromValue {
+        if let Some(strings) = decode_strings(bytes) {
+            return DiPromValue::Strings(strings);
+        }
+        let words = bytes.chunks_exact(4);
+        if !bytes.is_empty() && words.remainder().is_empty() {
+            return DiPromValue::Ints(
+                words
+                    .map(|x| i32::from_be_bytes([x[0], x[1], x[2], x[3]]))
+                    .collect(),
+            );
+        }
+        DiPromValue::Bytes(bytes.to_vec())
+    }
+}
+
+fn decode_strings(bytes: &[u8]) -> Option<Vec<String>> {
+    let body = bytes.strip_suffix(&[0])?;
+    let mut strings = Vec::new();
+    for s in body.split(|b| *b == 0) {
+        if s.is_empty() || !s.iter().all(|b| (0x20..0x7f).contains(b)) {
+            return None;
+        }
+        strings.push(String::from_utf8_lossy(s).into_owned());
+    }
+    Some(strings)
+}
+
+impl Display for DiPromValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Self::Strings(x) => write!(f, "{:?}", x),
+            Self::Ints(x) => write!(f, "{:x?}", x),
+            Self::Bytes(x) => write!(f, "{:02x?}", x),
+        }
+    }
+}
+
+const IDFORM_1: u8 = 1; /* format number for first ID proms */
+
+/// Size of the fields of an idprom that are defined by `IDFORM_1`. The
+/// trailing 16 undefined bytes are not required to be present.
+const IDPROM_DEFINED_LEN: usize = 16;
+
+/// Decoded contents of the `idprom` prom property. See `sys/idprom.h`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IdProm {
+    /// Format identifier, always `1` for a decoded idprom.
+    pub format: u8,
+    /// Machine type.
+    pub machine: u8,
+    /// Ethernet address.
+    pub ether: [u8; 6],
+    /// Date of manufacture in seconds since the epoch.
+    pub date: u32,
+    /// 24-bit serial number.
+    pub serial: u32,
+    /// XOR checksum over the preceding fields.
+    pub checksum: u8,
+}
+
+impl IdProm {
+    /// The host id, formed from the machine type and serial number.
+    pub fn host_id(&self) -> u32 {
+        (u32::from(self.machine) << 24) | self.serial
+    }
+
+    /// Whether the stored checksum matches the XOR of the preceding fields.
+    pub fn checksum_valid(&self) -> bool {
+        let mut xsum = self.format ^ self.machine;
+        for b in &self.ether {
+            xsum ^= b;
+        }
+        for b in &self.date.to_be_bytes() {
+            xsum ^= b;
+        }
+        for b in &self.serial.to_be_bytes()[1..] {
+            xsum ^= b;
+        }
+        xsum == self.checksum
+    }
+}
+
+/// Decode the raw bytes of an `idprom` prom property. Prom data is
+/// big-endian. Returns `None` if the data is too short or is not in a
+/// format this crate knows how to decode.
+pub fn parse_idprom(bytes: &[u8]) -> Option<IdProm> {
+    if bytes.len() < IDPROM_DEFINED_LEN || bytes[0] != IDFORM_1 {
+        return None;
+    }
+
+    let mut ether = [0u8; 6];
+    ether.copy_from_slice(&bytes[2..8]);
+    let date = u32::from_be_bytes(bytes[8..12].try_into().ok()?);
+    let serial = u32::from_be_bytes([0, bytes[12], bytes[13], bytes[14]]);
+
+    Some(IdProm {
+        format: bytes[0],
+        machine: bytes[1],
+        ether,
+        date,
+        serial,
+        checksum: bytes[15],
+    })
+}
@@ -0,0 +1,132 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Copyright 2022 Oxide Computer Company
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{DevInfoError, DeviceInfo, DeviceKey, SnapshotFlags};
+
+/// Options for collecting devices, for when the `get_devices_*` shorthands
+/// don't fit, e.g. the `nvme` devices below `/pci@0,0` with their prom
+/// properties. Options combine freely; [`DeviceQuery::run`] takes the
+/// snapshot.
+#[derive(Clone)]
+pub struct DeviceQuery {
+    pub(crate) root: String,
+    pub(crate) driver: Option<String>,
+    pub(crate) flags: SnapshotFlags,
+    pub(crate) fetch_prom: bool,
+    pub(crate) prom_props: Option<Vec<String>>,
+    pub(crate) prom_skip: Option<PromSkip>,
+    pub(crate) leaves_only: bool,
+}
+
+pub(crate) type PromSkip = Arc<dyn Fn(&DeviceKey) -> bool + Send + Sync>;
+
+impl DeviceQuery {
+    /// A query for every device, with the default snapshot flags and no
+    /// prom properties, i.e. the same as [`get_devices(false)`].
+    ///
+    /// [`get_devices(false)`]: crate::get_devices
+    pub fn new() -> Self {
+        DeviceQuery {
+            root: "/".to_string(),
+            driver: None,
+            flags: SnapshotFlags::default(),
+            fetch_prom: false,
+            prom_props: None,
+            prom_skip: None,
+            leaves_only: false,
+        }
+    }
+
+    /// Only snapshot the subtree rooted at the physical path `path`, e.g.
+    /// `/pci@0,0`. Defaults to `/`.
+    pub fn root(mut self, path: &str) -> Self {
+        self.root = path.to_string();
+        self
+    }
+
+    /// Only collect the devices bound to the named driver, e.g. `nvme`.
+    /// The snapshot's per-driver node list is used, so other nodes are not
+    /// read at all.
+    pub fn driver(mut self, driver: &str) -> Self {
+        self.driver = Some(driver.to_string());
+        self
+    }
+
+    /// The detail the snapshot includes, on top of the subtree and its
+    /// properties, which a query always needs. For example,
+    /// [`SnapshotFlags::PATH`] adds multipath information. Defaults to
+    /// [`SnapshotFlags::CPYALL`], i.e. minor data is included unless flags
+    /// are given without [`SnapshotFlags::MINOR`].
+    pub fn flags(mut self, flags: SnapshotFlags) -> Self {
+        self.flags = SnapshotFlags::SUBTREE | SnapshotFlags::PROP | flags;
+        self
+    }
+
+    /// Whether to also fetch each device's prom properties.
+    pub fn fetch_prom(mut self, fetch_prom: bool) -> Self {
+        self.fetch_prom = fetch_prom;
+        self
+    }
+
+    /// Fetch only the named prom properties, e.g. `&["model", "reg"]`,
+    /// looking each one up directly rather than reading every property of
+    /// every node. This implies [`DeviceQuery::fetch_prom`]. Names a node
+    /// doesn't have are left out of its `prom_props`.
+    pub fn only_prom_props(mut self, names: &[&str]) -> Self {
+        self.fetch_prom = true;
+        self.prom_props = Some(names.iter().map(|n| n.to_string()).collect());
+        self
+    }
+
+    /// Don't fetch prom properties for the devices `skip` returns true for,
+    /// e.g. to avoid the cost of reading them for pseudo devices. Skipped
+    /// devices are still collected, with empty `prom_props`. Only has an
+    /// effect with [`DeviceQuery::fetch_prom`].
+    pub fn prom_skip_predicate<F>(mut self, skip: F) -> Self
+    where
+        F: Fn(&DeviceKey) -> bool + Send + Sync + 'static,
+    {
+        self.prom_skip = Some(Arc::new(skip));
+        self
+    }
+
+    /// Only collect devices without children in the snapshot, e.g. the
+    /// disks rather than the controllers and bridges above them. Interior
+    /// nodes are passed over without being read.
+    pub fn leaves_only(mut self, leaves_only: bool) -> Self {
+        self.leaves_only = leaves_only;
+        self
+    }
+
+    /// Take the snapshot and collect the matching devices.
+    pub fn run(&self) -> Result<BTreeMap<DeviceKey, DeviceInfo>, DevInfoError> {
+        crate::sys::run_query(self)
+    }
+}
+
+impl fmt::Debug for DeviceQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceQuery")
+            .field("root", &self.root)
+            .field("driver", &self.driver)
+            .field("flags", &self.flags)
+            .field("fetch_prom", &self.fetch_prom)
+            .field("prom_props", &self.prom_props)
+            .field("prom_skip", &self.prom_skip.is_some())
+            .field("leaves_only", &self.leaves_only)
+            .finish()
+    }
+}
+
+impl Default for DeviceQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,72 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Copyright 2022 Oxide Computer Company
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{DevInfoError, DeviceInfo, DeviceKey};
+
+/// Bumped whenever a change to the library's types means older saved files
+/// can no longer be read. Fields added without a bump must be
+/// `#[serde(default)]`, so that files saved before they existed still load.
+const SAVE_VERSION: u32 = 3;
+
+#[derive(Serialize)]
+struct Saved<'a> {
+    version: u32,
+    devices: &'a BTreeMap<DeviceKey, DeviceInfo>,
+}
+
+/// The devices are decoded only once the version has been checked, so that
+/// a file from another version is reported as such rather than as a
+/// missing or unexpected field.
+#[derive(Deserialize)]
+struct Loaded {
+    version: u32,
+    devices: serde_json::Value,
+}
+
+/// Write a device map, as returned by [`get_devices`] and friends, in a
+/// versioned JSON format that [`load_devices`] reads back. This allows
+/// devices to be captured on one machine and examined on another, including
+/// one without libdevinfo.
+///
+/// [`get_devices`]: crate::get_devices
+pub fn save_devices<W: Write>(
+    devices: &BTreeMap<DeviceKey, DeviceInfo>,
+    w: W,
+) -> Result<(), DevInfoError> {
+    let saved = Saved {
+        version: SAVE_VERSION,
+        devices,
+    };
+    serde_json::to_writer(w, &saved).map_err(std::io::Error::from)?;
+    Ok(())
+}
+
+/// Read a device map written by [`save_devices`]. Files written by an
+/// incompatible version of this crate are rejected.
+pub fn load_devices<R: Read>(
+    r: R,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>, DevInfoError> {
+    let loaded: Loaded =
+        serde_json::from_reader(r).map_err(std::io::Error::from)?;
+    if loaded.version != SAVE_VERSION {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "saved devices are version {}, expected {}",
+                loaded.version, SAVE_VERSION
+            ),
+        )
+        .into());
+    }
+    let devices =
+        serde_json::from_value(loaded.devices).map_err(std::io::Error::from)?;
+    Ok(devices)
+}
@@ -0,0 +1,65 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Copyright 2022 Oxide Computer Company
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{DeviceClass, DeviceInfo, DeviceKey};
+
+/// Device counts by driver and by class, for a one-glance overview such as
+/// "12 nvme, 4 igb, 2 cpu". Built by [`summarize`], or one device at a time
+/// with [`TreeSummary::add`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TreeSummary {
+    pub total: usize,
+    /// Counts by the driver bound to each device, or failing that the
+    /// driver it would bind to.
+    pub by_driver: BTreeMap<String, usize>,
+    /// Devices with neither a bound driver nor a binding name.
+    pub no_driver: usize,
+    /// Counts by [`DeviceInfo::device_class`].
+    pub by_class: BTreeMap<DeviceClass, usize>,
+}
+
+impl TreeSummary {
+    /// Count one more device. Walking with
+    /// [`for_each_device`](crate::for_each_device) and adding each device
+    /// counts nodes that share a key, which a device map holds only one of.
+    pub fn add(&mut self, dev: &DeviceInfo) {
+        self.total += 1;
+        match dev.driver_name.as_ref().or(dev.binding_name.as_ref()) {
+            Some(name) => *self.by_driver.entry(name.clone()).or_default() += 1,
+            None => self.no_driver += 1,
+        }
+        *self.by_class.entry(dev.device_class()).or_default() += 1;
+    }
+
+    /// The driver counts, most common first.
+    pub fn drivers_by_count(&self) -> Vec<(&str, usize)> {
+        let mut counts: Vec<_> = self
+            .by_driver
+            .iter()
+            .map(|(name, count)| (name.as_str(), *count))
+            .collect();
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        counts
+    }
+}
+
+/// Summarize a device map, as returned by [`get_devices`] and friends, into
+/// counts by driver and by class.
+///
+/// [`get_devices`]: crate::get_devices
+pub fn summarize(devices: &BTreeMap<DeviceKey, DeviceInfo>) -> TreeSummary {
+    let mut summary = TreeSummary::default();
+    for dev in devices.values() {
+        summary.add(dev);
+    }
+    summary
+}
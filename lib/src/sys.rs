@@ -7,21 +7,49 @@
 #![allow(dead_code)]
 #![allow(non_camel_case_types)]
 
+use std::any::Any;
 use std::collections::BTreeMap;
 use std::convert::TryFrom;
-use std::ffi::{c_void, CStr};
-use std::io::{Error, Result};
+use std::ffi::{c_void, CStr, CString};
+use std::io::Error;
+use std::marker::PhantomData;
+use std::ops::ControlFlow;
 use std::os::raw::{c_char, c_int, c_uchar, c_uint, c_ulong};
+use std::os::unix::io::AsRawFd;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
 use std::ptr::{null, null_mut};
 use std::slice;
 
-use crate::{DeviceInfo, DiPropType, DiPropValue};
+use crate::query::PromSkip;
+#[cfg(feature = "private-data")]
+use crate::PrivateData;
+use crate::{
+    DevInfoError, DevInfoWarning, DeviceInfo, DeviceKey, DeviceLink,
+    DeviceNode, DeviceQuery, DeviceTree, DiPromValue, DiPropType, DiPropValue,
+    DiPropValueRef, DriverOps, HotplugConnector, HotplugState, LinkEnd,
+    MinorNode, MultipathRole, NodeAttributes, NodeClass, NodeFlags, NodeId,
+    NodeState, PathInfo, PathState, PropSource, PropStrings, ShadowedProp,
+    SnapshotFlags, SpecType,
+};
+
+type Result<T> = std::result::Result<T, DevInfoError>;
 
 const DIIOC: u32 = 0xdf << 8;
 const DINFOSUBTREE: u32 = DIIOC | 0x01; /* include subtree */
 const DINFOMINOR: u32 = DIIOC | 0x02; /* include minor data */
 const DINFOPROP: u32 = DIIOC | 0x04; /* include properties */
 const DINFOPATH: u32 = DIIOC | 0x08; /* include i/o pathing information */
+const DINFOPRIVDATA: u32 = DIIOC | 0x10; /* include private data */
+const DINFOFORCE: u32 = DIIOC | 0x20; /* force load all drivers */
+const DINFOCPYONE: u32 = DIIOC; /* just copy one node */
+const DINFOCPYALL: u32 = DINFOSUBTREE | DINFOPROP | DINFOMINOR;
+
+/// What snapshots include unless the caller asks otherwise.
+const SNAPSHOT_FLAGS: c_uint = DINFOCPYALL;
+
+const DI_PSEUDO_NODEID: c_int = -1;
+const DI_SID_NODEID: c_int = -2;
+const DI_PROM_NODEID: c_int = -3;
 
 const DI_WALK_CONTINUE: c_int = 0;
 const DI_WALK_PRUNESIB: c_int = -1;
@@ -42,8 +70,30 @@ const DI_WALK_CLDFIRST: c_uint = 0;
 const DI_WALK_SIBFIRST: c_uint = 1;
 const DI_WALK_LINKGEN: c_uint = 2;
 
+const DI_LINK_SRC: c_uint = 1;
+const DI_LINK_TGT: c_uint = 2;
+
 const OPROMMAXPARAM: c_uint = 32768;
 
+const OIOC: c_int = (b'O' as c_int) << 8;
+const OPROMNEXT: c_int = OIOC | 5; /* get next sibling node id */
+const OPROMCHILD: c_int = OIOC | 6; /* get first child node id */
+const OPROMGETPROP: c_int = OIOC | 7; /* get property value */
+const OPROMNXTPROP: c_int = OIOC | 8; /* get next property name */
+const OPROMGETVERSION: c_int = OIOC | 13; /* get prom version string */
+
+const DDI_DEV_T_ANY: dev_t = !1; /* (dev_t)-2 */
+
+const EPERM: c_int = 1;
+const ENOENT: c_int = 2;
+const ENXIO: c_int = 6;
+const ENOMEM: c_int = 12;
+const EACCES: c_int = 13;
+const EINVAL: c_int = 22;
+
+const S_IFCHR: c_int = 0x2000; /* character special */
+const S_IFBLK: c_int = 0x6000; /* block special */
+
 type di_off_t = u32;
 
 #[repr(C)]
@@ -117,7 +167,7 @@ struct di_node {
      */
     instance: c_int,              /* instance number */
     nodeid: c_int,                /* node id */
-    node_class: ddi_node_class_t, /* node class */
+    node_class: c_int,            /* node class (ddi_node_class_t) */
     attributes: c_int,            /* node attributes */
     state: c_uint,                /* hotplugging device state */
     node_state: ddi_node_state_t, /* devinfo state */
@@ -263,13 +313,46 @@ struct lwp_mutex_lock_lock32 {
     lockword: u32,
 }
 
+/// Path nodes are only ever handled through accessors.
+#[repr(C)]
+struct di_path {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct di_hp {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct di_link {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct di_lnode {
+    _private: [u8; 0],
+}
+
+/// An encoded device id, only ever handled through libdevid.
+#[repr(C)]
+struct impl_devid {
+    _private: [u8; 0],
+}
+
+type ddi_devid_t = *mut impl_devid;
 type di_node_t = *mut di_node;
+type di_path_t = *mut di_path;
+type di_hp_t = *mut di_hp;
+type di_link_t = *mut di_link;
+type di_lnode_t = *mut di_lnode;
 type di_minor_t = *mut di_minor;
 type di_prop_t = *mut di_prop;
 type di_prom_handle_t = *mut di_prom_handle;
 type di_prom_prop_t = *mut di_prom_prop;
-type major_t = c_ulong;
-type minor_t = c_ulong;
+type dev_t = c_ulong;
+type major_t = c_uint;
+type minor_t = c_uint;
 type mutex_t = lwp_mutex_t;
 
 extern "C" {
@@ -280,14 +363,68 @@ extern "C" {
         arg: *mut c_void,
         node_callback: extern "C" fn(di_node_t, *mut c_void) -> c_int,
     ) -> c_int;
+    fn di_walk_minor(
+        root: di_node_t,
+        minor_type: *const c_char,
+        flag: c_uint,
+        arg: *mut c_void,
+        minor_callback: extern "C" fn(
+            di_node_t,
+            di_minor_t,
+            *mut c_void,
+        ) -> c_int,
+    ) -> c_int;
     fn di_fini(root: di_node_t);
     fn di_node_name(node: di_node_t) -> *const c_char;
+    fn di_binding_name(node: di_node_t) -> *const c_char;
+    fn di_bus_addr(node: di_node_t) -> *const c_char;
+    fn di_driver_name(node: di_node_t) -> *const c_char;
+    fn di_driver_major(node: di_node_t) -> c_int;
+    fn di_driver_ops(node: di_node_t) -> c_uint;
     fn di_minor_next(node: di_node_t, minor: di_minor_t) -> di_minor_t;
+    fn di_minor_name(minor: di_minor_t) -> *const c_char;
+    fn di_minor_nodetype(minor: di_minor_t) -> *const c_char;
+    fn di_minor_spectype(minor: di_minor_t) -> c_int;
     fn di_instance(node: di_node_t) -> c_int;
-    fn di_devfs_path(node: di_node_t) -> *const c_char;
+    fn di_nodeid(node: di_node_t) -> c_int;
+    fn di_node_state(node: di_node_t) -> c_int;
+    fn di_compatible_names(node: di_node_t, names: *mut *mut c_char) -> c_int;
+    fn di_devfs_path(node: di_node_t) -> *mut c_char;
+    fn di_devfs_minor_path(minor: di_minor_t) -> *mut c_char;
+    fn di_devfs_path_free(path_buf: *mut c_char);
+    fn di_devid(node: di_node_t) -> ddi_devid_t;
+    fn di_parent_private_data(node: di_node_t) -> *mut c_void;
+    fn di_driver_private_data(node: di_node_t) -> *mut c_void;
     fn di_drv_first_node(drv_name: *const c_char, root: di_node_t)
         -> di_node_t;
     fn di_drv_next_node(node: di_node_t) -> di_node_t;
+    fn di_parent_node(node: di_node_t) -> di_node_t;
+    fn di_child_node(node: di_node_t) -> di_node_t;
+    fn di_sibling_node(node: di_node_t) -> di_node_t;
+
+    fn di_path_client_next_path(node: di_node_t, path: di_path_t) -> di_path_t;
+    fn di_path_phci_node(path: di_path_t) -> di_node_t;
+    fn di_path_bus_addr(path: di_path_t) -> *const c_char;
+    fn di_path_state(path: di_path_t) -> c_int;
+    fn di_walk_link(
+        root: di_node_t,
+        flag: c_uint,
+        endpoint: c_uint,
+        arg: *mut c_void,
+        link_callback: extern "C" fn(di_link_t, *mut c_void) -> c_int,
+    ) -> c_int;
+    fn di_link_next_by_node(
+        node: di_node_t,
+        link: di_link_t,
+        endpoint: c_uint,
+    ) -> di_link_t;
+    fn di_link_to_lnode(link: di_link_t, endpoint: c_uint) -> di_lnode_t;
+    fn di_link_spectype(link: di_link_t) -> c_int;
+    fn di_lnode_name(lnode: di_lnode_t) -> *const c_char;
+    fn di_lnode_devinfo(lnode: di_lnode_t) -> di_node_t;
+    fn di_hp_next(node: di_node_t, hp: di_hp_t) -> di_hp_t;
+    fn di_hp_name(hp: di_hp_t) -> *const c_char;
+    fn di_hp_state(hp: di_hp_t) -> c_int;
 
     fn di_prop_next(node: di_node_t, prop: di_prop_t) -> di_prop_t;
     fn di_prop_name(prop: di_prop_t) -> *const c_char;
@@ -296,6 +433,36 @@ extern "C" {
     fn di_prop_ints(prop: di_prop_t, prop_data: *mut *mut c_int) -> c_int;
     fn di_prop_int64(prop: di_prop_t, prop_data: *mut *mut i64) -> c_int;
     fn di_prop_strings(prop: di_prop_t, prop_data: *mut *mut c_char) -> c_int;
+    fn di_prop_lookup_bytes(
+        dev: dev_t,
+        node: di_node_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut c_uchar,
+    ) -> c_int;
+    fn di_prop_lookup_ints(
+        dev: dev_t,
+        node: di_node_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut c_int,
+    ) -> c_int;
+    fn di_prop_lookup_int64(
+        dev: dev_t,
+        node: di_node_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut i64,
+    ) -> c_int;
+    fn di_prop_lookup_strings(
+        dev: dev_t,
+        node: di_node_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut c_char,
+    ) -> c_int;
+
+    fn devid_str_encode(
+        devid: ddi_devid_t,
+        minor_name: *mut c_char,
+    ) -> *mut c_char;
+    fn devid_str_free(devidstr: *mut c_char);
 
     fn di_prom_init() -> di_prom_handle_t;
     fn di_prom_prop_next(
@@ -309,36 +476,773 @@ extern "C" {
         prom_prop: di_prom_prop_t,
         prom_prop_data: *mut *mut c_uchar,
     ) -> c_int;
+    fn di_prom_prop_lookup_bytes(
+        ph: di_prom_handle_t,
+        node: di_node_t,
+        prom_prop_name: *const c_char,
+        prom_prop_data: *mut *mut c_uchar,
+    ) -> c_int;
 }
 
-#[derive(PartialEq, Eq, Hash, Ord, PartialOrd)]
-pub struct DeviceKey {
-    pub node_name: String,
-    pub unit_address: Option<String>,
+extern "C" {
+    fn ioctl(fd: c_int, request: c_int, ...) -> c_int;
+    fn geteuid() -> c_uint;
 }
 
-struct Context {
-    info: BTreeMap<DeviceKey, DeviceInfo>,
-    fetch_prom: bool,
+/// A prom handle shared by every node of a walk. It is opened on first use
+/// and closed when dropped, so `/dev/openprom` is opened at most once per
+/// walk rather than once per node.
+struct Prom {
+    handle: di_prom_handle_t,
+    /// Set once `di_prom_init` has failed, to the errno it left behind, so
+    /// that the failure is reported for each node rather than retried.
+    init_error: Option<Option<i32>>,
+    /// If set, only these properties are looked up, rather than every
+    /// property of each node.
+    only: Option<Vec<CString>>,
+    /// Nodes for which prom properties are not read at all.
+    skip: Option<PromSkip>,
+}
+
+impl Prom {
+    fn new() -> Self {
+        Prom {
+            handle: null_mut(),
+            init_error: None,
+            only: None,
+            skip: None,
+        }
+    }
+
+    fn handle(&mut self) -> std::result::Result<di_prom_handle_t, Option<i32>> {
+        if let Some(errno) = self.init_error {
+            return Err(errno);
+        }
+        if self.handle.is_null() {
+            self.handle = unsafe { di_prom_init() };
+            if self.handle.is_null() {
+                let errno = Error::last_os_error().raw_os_error();
+                self.init_error = Some(errno);
+                return Err(errno);
+            }
+        }
+        Ok(self.handle)
+    }
+}
+
+impl Drop for Prom {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            unsafe { di_prom_fini(self.handle) };
+        }
+    }
+}
+
+/// The PROM device tree, read directly through the `openpromio` ioctls on
+/// `/dev/openprom` rather than through a devinfo snapshot. Opening it
+/// typically requires root privilege.
+///
+/// As with the underlying driver, the tree has a current node: the node
+/// most recently returned by [`PromTree::root`], [`PromTree::first_child`]
+/// or [`PromTree::next_sibling`]. The property methods read the current
+/// node.
+pub struct PromTree {
+    file: std::fs::File,
+    buf: Box<OppBuf>,
+}
+
+impl PromTree {
+    /// Open `/dev/openprom`.
+    pub fn open() -> Result<PromTree> {
+        let file = std::fs::File::open("/dev/openprom").map_err(|e| match e
+            .kind()
+        {
+            std::io::ErrorKind::PermissionDenied => {
+                DevInfoError::PermissionDenied
+            }
+            _ => DevInfoError::Io(e),
+        })?;
+        Ok(PromTree {
+            file,
+            buf: Box::new(OppBuf {
+                buf: [0; OPROMMAXPARAM as usize],
+            }),
+        })
+    }
+
+    /// The prom's version string.
+    pub fn version(&mut self) -> Result<String> {
+        self.set_data(b"")?;
+        self.ioctl(OPROMGETVERSION)?;
+        Ok(self.data_str())
+    }
+
+    /// The root node, which becomes the current node.
+    pub fn root(&mut self) -> Result<Option<i32>> {
+        self.step(OPROMNEXT, 0)
+    }
+
+    /// The first child of `node`, which becomes the current node. Returns
+    /// `None` if `node` has no children.
+    pub fn first_child(&mut self, node: i32) -> Result<Option<i32>> {
+        self.step(OPROMCHILD, node)
+    }
+
+    /// The next sibling of `node`, which becomes the current node. Returns
+    /// `None` if `node` is the last of its siblings.
+    pub fn next_sibling(&mut self, node: i32) -> Result<Option<i32>> {
+        self.step(OPROMNEXT, node)
+    }
+
+    /// The name of the current node's property after `prev`, or of its first
+    /// property if `prev` is `None`. Returns `None` after the last property.
+    pub fn next_property(
+        &mut self,
+        prev: Option<&str>,
+    ) -> Result<Option<String>> {
+        let prev = CString::new(prev.unwrap_or_default())?;
+        self.set_data(prev.as_bytes())?;
+        self.ioctl(OPROMNXTPROP)?;
+        Ok(Some(self.data_str()).filter(|name| !name.is_empty()))
+    }
+
+    /// The value of the current node's property `name`, or `None` if the
+    /// node has no such property.
+    pub fn property(&mut self, name: &str) -> Result<Option<DiPromValue>> {
+        let name = CString::new(name)?;
+        self.set_data(name.as_bytes())?;
+        self.ioctl(OPROMGETPROP)?;
+
+        // The driver reports a missing property with a size of -1.
+        let size = unsafe { self.buf.opp.oprom_size };
+        if size == c_uint::MAX {
+            return Ok(None);
+        }
+        let data = self.data();
+        let len = (size as usize).min(data.len());
+        Ok(Some(DiPromValue::decode(&data[..len])))
+    }
+
+    fn step(&mut self, cmd: c_int, node: i32) -> Result<Option<i32>> {
+        self.buf.opp.oprom_size = std::mem::size_of::<c_int>() as c_uint;
+        self.buf.opp.opio_u.i = node;
+        self.ioctl(cmd)?;
+        let next = unsafe { self.buf.opp.opio_u.i };
+        Ok(Some(next).filter(|n| *n != 0))
+    }
+
+    fn ioctl(&mut self, cmd: c_int) -> Result<()> {
+        let fd = self.file.as_raw_fd();
+        let arg = &mut *self.buf as *mut OppBuf;
+        if unsafe { ioctl(fd, cmd, arg) } < 0 {
+            return Err(Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    /// Copy `bytes` and a terminating nul into the data area, and set the
+    /// size to the whole of the data area so that the driver has room for
+    /// its reply.
+    fn set_data(&mut self, bytes: &[u8]) -> Result<()> {
+        let data = self.data_mut();
+        if bytes.len() >= data.len() {
+            return Err(std::io::Error::from_raw_os_error(EINVAL).into());
+        }
+        data[..bytes.len()].copy_from_slice(bytes);
+        data[bytes.len()] = 0;
+        self.buf.opp.oprom_size = data.len() as c_uint;
+        Ok(())
+    }
+
+    /// The data area that follows `oprom_size`.
+    fn data(&self) -> &[u8] {
+        let len = OPROMMAXPARAM as usize - std::mem::size_of::<c_uint>();
+        unsafe {
+            let p = &self.buf.opp.opio_u as *const openpromio_opio;
+            slice::from_raw_parts(p as *const u8, len)
+        }
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        let len = OPROMMAXPARAM as usize - std::mem::size_of::<c_uint>();
+        unsafe {
+            let p = &mut self.buf.opp.opio_u as *mut openpromio_opio;
+            slice::from_raw_parts_mut(p as *mut u8, len)
+        }
+    }
+
+    fn data_str(&self) -> String {
+        let data = self.data();
+        let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+        String::from_utf8_lossy(&data[..end]).into_owned()
+    }
+}
+
+/// A string that libdevinfo or libdevid allocated for the caller, freed when
+/// dropped so that an early return or panic can't leak it. Most accessors
+/// return pointers into the snapshot that must not be freed; the ones that
+/// allocate are `di_devfs_path` and `di_devfs_minor_path`, freed with
+/// `di_devfs_path_free`, and `devid_str_encode`, freed with
+/// `devid_str_free`.
+struct OwnedStr {
+    ptr: *mut c_char,
+    free: unsafe extern "C" fn(*mut c_char),
+}
+
+impl OwnedStr {
+    /// Take ownership of `ptr`, which is freed with `free`. Returns `None`
+    /// for a null pointer.
+    fn new(
+        ptr: *mut c_char,
+        free: unsafe extern "C" fn(*mut c_char),
+    ) -> Option<OwnedStr> {
+        if ptr.is_null() {
+            None
+        } else {
+            Some(OwnedStr { ptr, free })
+        }
+    }
+
+    fn to_string_lossy(&self) -> String {
+        unsafe { CStr::from_ptr(self.ptr) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+impl Drop for OwnedStr {
+    fn drop(&mut self) {
+        unsafe { (self.free)(self.ptr) };
+    }
+}
+
+fn devfs_path(node: di_node_t) -> Option<String> {
+    OwnedStr::new(unsafe { di_devfs_path(node) }, di_devfs_path_free)
+        .map(|s| s.to_string_lossy())
+}
+
+/// A prom handle for a walk that wants prom properties. Without the
+/// privilege to read them, fail up front rather than leaving every node's
+/// `prom_props` empty.
+fn prom_for(fetch_prom: bool) -> Result<Option<Prom>> {
+    if !fetch_prom {
+        Ok(None)
+    } else if can_fetch_prom() {
+        Ok(Some(Prom::new()))
+    } else {
+        Err(DevInfoError::PermissionDenied)
+    }
+}
+
+type NodeFn<'a> = dyn FnMut(DeviceKey, DeviceInfo) -> ControlFlow<()> + 'a;
+
+struct Context<'a> {
+    prom: Option<Prom>,
+    root: String,
+    leaves_only: bool,
+    siblings: SiblingIndexer,
+    error: Option<DevInfoError>,
+    panic: Option<Box<dyn Any + Send>>,
+    f: &'a mut NodeFn<'a>,
 }
 
 pub fn get_devices(
     fetch_prom: bool,
 ) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
-    let path = std::ffi::CString::new("/").unwrap();
-    let root_node = unsafe {
-        di_init(
-            path.as_c_str().as_ptr() as *const c_char,
-            DINFOSUBTREE | DINFOPROP,
-        )
+    DeviceQuery::new().fetch_prom(fetch_prom).run()
+}
+
+/// Collect devices from a snapshot taken with the given detail flags.
+pub fn get_devices_with(
+    flags: SnapshotFlags,
+    fetch_prom: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    // Unlike `DeviceQuery::flags`, the flags are used as given.
+    let query = DeviceQuery {
+        flags,
+        ..DeviceQuery::new()
     };
-    if root_node.is_null() {
-        return Err(Error::last_os_error());
+    query.fetch_prom(fetch_prom).run()
+}
+
+pub(crate) fn run_query(
+    query: &DeviceQuery,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    let root = query.root.as_str();
+    let flags = DINFOCPYONE | query.flags.bits();
+
+    let mut prom = prom_for(query.fetch_prom)?;
+    if let Some(prom) = &mut prom {
+        if let Some(names) = &query.prom_props {
+            let mut only = Vec::with_capacity(names.len());
+            for name in names {
+                only.push(CString::new(name.as_str())?);
+            }
+            prom.only = Some(only);
+        }
+        prom.skip = query.prom_skip.clone();
+    }
+
+    if let Some(driver) = &query.driver {
+        let driver = CString::new(driver.as_str())?;
+        let root_node = snapshot(root, flags)?;
+        let info = driver_nodes(
+            root_node,
+            &driver,
+            &mut prom,
+            root,
+            query.leaves_only,
+        );
+        unsafe { di_fini(root_node) };
+        return info;
+    }
+
+    let mut info = BTreeMap::new();
+    walk(
+        root,
+        flags,
+        prom,
+        query.leaves_only,
+        &mut |key, dev_info| {
+            info.insert(key, dev_info);
+            ControlFlow::Continue(())
+        },
+    )?;
+    Ok(info)
+}
+
+/// Fetch the single device at the physical path `path`, e.g. a
+/// `devfs_path` from an earlier walk. Returns `Ok(None)` if there is no
+/// device at that path.
+pub fn get_device(path: &str, fetch_prom: bool) -> Result<Option<DeviceInfo>> {
+    // Only the node itself is read, so don't copy its subtree.
+    let flags = DINFOCPYONE | DINFOPROP | DINFOMINOR;
+    let mut prom = prom_for(fetch_prom)?;
+    let root_node = match snapshot(path, flags) {
+        Ok(node) => node,
+        Err(DevInfoError::NotFound(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let index = sibling_index(root_node);
+    let info = read_node(root_node, index, prom.as_mut(), path);
+    unsafe { di_fini(root_node) };
+
+    Ok(Some(info?.1))
+}
+
+/// Collect the devices in the subtree rooted at the physical path `path`,
+/// e.g. `/pci@0,0`. Snapshotting a subtree is cheaper than snapshotting the
+/// whole tree and filtering.
+pub fn get_devices_at(
+    path: &str,
+    fetch_prom: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    DeviceQuery::new().root(path).fetch_prom(fetch_prom).run()
+}
+
+/// Visit every device without collecting them into a map. The callback is
+/// invoked from within the walk, and returning `ControlFlow::Break` stops
+/// the walk early.
+pub fn for_each_device<F>(fetch_prom: bool, mut f: F) -> Result<()>
+where
+    F: FnMut(&DeviceKey, &DeviceInfo) -> ControlFlow<()>,
+{
+    let prom = prom_for(fetch_prom)?;
+    walk("/", SNAPSHOT_FLAGS, prom, false, &mut |key, info| {
+        f(&key, &info)
+    })
+}
+
+/// Count the devices in the tree without collecting them. Nodes that share
+/// a key are each counted, so this can exceed the length of the map
+/// [`get_devices`] returns.
+pub fn count_devices(fetch_prom: bool) -> Result<usize> {
+    let mut count = 0;
+    for_each_device(fetch_prom, |_, _| {
+        count += 1;
+        ControlFlow::Continue(())
+    })?;
+    Ok(count)
+}
+
+type MinorFn<'a> = dyn FnMut(&DeviceKey, &MinorNode) -> ControlFlow<()> + 'a;
+
+struct MinorContext<'a> {
+    error: Option<DevInfoError>,
+    panic: Option<Box<dyn Any + Send>>,
+    f: &'a mut MinorFn<'a>,
+}
+
+/// Visit minor nodes directly, optionally only those of the given node
+/// type, e.g. `ddi_block`. The callback gets the key of the device that
+/// owns each minor. No properties are read, which makes this much cheaper
+/// than [`get_devices`] for questions like "which block devices are
+/// there". Returning `ControlFlow::Break` stops the walk early.
+pub fn walk_minors<F>(node_type: Option<&str>, mut f: F) -> Result<()>
+where
+    F: FnMut(&DeviceKey, &MinorNode) -> ControlFlow<()>,
+{
+    let node_type = node_type.map(CString::new).transpose()?;
+    let root_node = snapshot("/", DINFOSUBTREE | DINFOMINOR)?;
+
+    let mut ctx = MinorContext {
+        error: None,
+        panic: None,
+        f: &mut f,
+    };
+
+    unsafe {
+        di_walk_minor(
+            root_node,
+            node_type.as_ref().map_or(null(), |t| t.as_ptr()),
+            0,
+            &mut ctx as *mut MinorContext as *mut c_void,
+            minor_info,
+        );
+        di_fini(root_node);
+    };
+
+    if let Some(payload) = ctx.panic {
+        resume_unwind(payload);
+    }
+
+    match ctx.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+extern "C" fn minor_info(
+    node: di_node_t,
+    minor: di_minor_t,
+    arg: *mut c_void,
+) -> c_int {
+    let ctx = unsafe { &mut *(arg as *mut MinorContext) };
+
+    let key = match node_key(node) {
+        Ok(key) => key,
+        Err(e) => {
+            ctx.error = Some(e);
+            return DI_WALK_TERMINATE;
+        }
+    };
+    // There's nowhere to put a warning here, so unrepresentable minors are
+    // skipped.
+    let minor = match read_minor(minor) {
+        Ok(m) => m,
+        Err(_) => return DI_WALK_CONTINUE,
+    };
+
+    let f = &mut ctx.f;
+    match catch_unwind(AssertUnwindSafe(|| f(&key, &minor))) {
+        Ok(ControlFlow::Continue(())) => DI_WALK_CONTINUE,
+        Ok(ControlFlow::Break(())) => DI_WALK_TERMINATE,
+        Err(payload) => {
+            ctx.panic = Some(payload);
+            DI_WALK_TERMINATE
+        }
+    }
+}
+
+/// A device's properties, borrowed from the snapshot for the duration of a
+/// [`for_each_device_ref`] callback. Properties are read as they are
+/// iterated over.
+pub struct PropsRef<'a> {
+    node: di_node_t,
+    /// Null unless the walk was asked to fetch prom properties.
+    prom: di_prom_handle_t,
+    _snapshot: PhantomData<&'a ()>,
+}
+
+impl<'a> PropsRef<'a> {
+    /// The device's properties in snapshot order. Properties that can't be
+    /// read, or whose names aren't UTF-8, are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, DiPropValueRef<'a>)> {
+        let node = self.node;
+        let mut prop: di_prop_t = null_mut();
+        std::iter::from_fn(move || loop {
+            prop = unsafe { di_prop_next(node, prop) };
+            if prop.is_null() {
+                return None;
+            }
+            let name = unsafe { CStr::from_ptr(di_prop_name(prop)) };
+            if let (Ok(name), Some(value)) =
+                (name.to_str(), read_prop_ref(prop))
+            {
+                return Some((name, value));
+            }
+        })
+    }
+
+    /// Look up a property by name. As with [`get_devices`], if a name
+    /// appears on more than one property list the first value wins.
+    pub fn get(&self, name: &str) -> Option<DiPropValueRef<'a>> {
+        self.iter().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+
+    /// Look up a property by name on exactly one property list, regardless
+    /// of whether a list with higher precedence also has it.
+    pub fn get_from(
+        &self,
+        name: &str,
+        source: PropSource,
+    ) -> Option<DiPropValueRef<'a>> {
+        let mut prop: di_prop_t = null_mut();
+        loop {
+            prop = unsafe { di_prop_next(self.node, prop) };
+            if prop.is_null() {
+                return None;
+            }
+            let list = unsafe { (*prop).prop_list };
+            let cs = unsafe { CStr::from_ptr(di_prop_name(prop)) };
+            if list == source as i32 && cs.to_bytes() == name.as_bytes() {
+                return read_prop_ref(prop);
+            }
+        }
+    }
+
+    /// The device's prom properties as raw bytes, borrowed rather than
+    /// copied into a map as [`DeviceInfo::prom_props`] is. Empty unless
+    /// the walk was asked to fetch prom properties. Properties that can't
+    /// be read, or whose names aren't UTF-8, are skipped.
+    pub fn prom_iter(&self) -> impl Iterator<Item = (&'a str, &'a [u8])> {
+        let (node, ph) = (self.node, self.prom);
+        let mut prom_prop: di_prom_prop_t = null_mut();
+        std::iter::from_fn(move || loop {
+            if ph.is_null() {
+                return None;
+            }
+            prom_prop = unsafe { di_prom_prop_next(ph, node, prom_prop) };
+            if prom_prop.is_null() {
+                return None;
+            }
+            let name = unsafe { CStr::from_ptr(di_prom_prop_name(prom_prop)) };
+            let mut data: *mut c_uchar = null_mut();
+            let len = unsafe { di_prom_prop_data(prom_prop, &mut data) };
+            if let (Ok(name), true) = (name.to_str(), len >= 0) {
+                return Some((name, unsafe { borrow(data, len) }));
+            }
+        })
+    }
+
+    /// Look up a single prom property by name, without iterating over the
+    /// others. Always `None` unless the walk was asked to fetch prom
+    /// properties.
+    pub fn get_prom(&self, name: &str) -> Option<&'a [u8]> {
+        if self.prom.is_null() {
+            return None;
+        }
+        let name = CString::new(name).ok()?;
+        let mut data: *mut c_uchar = null_mut();
+        let len = unsafe {
+            di_prom_prop_lookup_bytes(
+                self.prom,
+                self.node,
+                name.as_ptr(),
+                &mut data,
+            )
+        };
+        if len < 0 {
+            return None;
+        }
+        Some(unsafe { borrow(data, len) })
+    }
+
+    /// Look up a string property by name and borrow its first string. See
+    /// [`DiPropValueRef::as_cstr`].
+    pub fn get_cstr(&self, name: &str) -> Option<&'a CStr> {
+        self.get(name)?.as_cstr()
+    }
+
+    /// The name of the driver bound to the device, if any, borrowed from
+    /// the snapshot.
+    pub fn driver_name(&self) -> Option<&'a CStr> {
+        let name = unsafe { di_driver_name(self.node) };
+        if name.is_null() {
+            None
+        } else {
+            Some(unsafe { CStr::from_ptr(name) })
+        }
+    }
+}
+
+/// Build a slice from libdevinfo's data pointer and count. Empty values,
+/// such as those of undefined properties, come with a count of zero and a
+/// data pointer that was never set, so that must not reach
+/// `slice::from_raw_parts`.
+unsafe fn borrow<'a, T>(data: *const T, count: c_int) -> &'a [T] {
+    if data.is_null() || count <= 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, count as usize)
     }
+}
+
+/// Borrow a property's value from the snapshot.
+fn read_prop_ref<'a>(prop: di_prop_t) -> Option<DiPropValueRef<'a>> {
+    let value = match DiPropType::try_from(unsafe { di_prop_type(prop) }) {
+        Ok(DiPropType::Boolean) => DiPropValueRef::Boolean(true),
+        Ok(DiPropType::Int) => {
+            let mut data: *mut i32 = null_mut();
+            let count = unsafe { di_prop_ints(prop, &mut data) };
+            if count < 0 {
+                return None;
+            }
+            DiPropValueRef::Ints(unsafe { borrow(data, count) })
+        }
+        Ok(DiPropType::Int64) => {
+            let mut data: *mut i64 = null_mut();
+            let count = unsafe { di_prop_int64(prop, &mut data) };
+            if count < 0 {
+                return None;
+            }
+            DiPropValueRef::Int64s(unsafe { borrow(data, count) })
+        }
+        Ok(DiPropType::String) => {
+            let mut data: *mut c_char = null_mut();
+            let count = unsafe { di_prop_strings(prop, &mut data) };
+            if count < 0 {
+                return None;
+            }
+            // `count` is the number of strings, so find where the last one
+            // ends.
+            let mut len = 0;
+            for _ in 0..count {
+                let cs = unsafe { CStr::from_ptr(data.add(len)) };
+                len += cs.to_bytes_with_nul().len();
+            }
+            let bytes = unsafe { borrow(data as *const u8, len as c_int) };
+            DiPropValueRef::Strings(PropStrings::new(bytes))
+        }
+        Ok(DiPropType::Byte | DiPropType::Unknown | DiPropType::UndefIt) => {
+            let mut data: *mut c_uchar = null_mut();
+            let count = unsafe { di_prop_bytes(prop, &mut data) };
+            if count < 0 {
+                return None;
+            }
+            DiPropValueRef::Bytes(unsafe { borrow(data, count) })
+        }
+        Err(_) => return None,
+    };
+    Some(value)
+}
+
+/// Like [`for_each_device`], but hand the callback the properties of each
+/// device borrowed from the snapshot rather than copied into a
+/// [`DeviceInfo`]. Only properties are snapshotted, and nothing is
+/// allocated per property, which makes this the cheapest way to scan every
+/// device for a few properties. With `fetch_prom`, prom properties are
+/// borrowed the same way, through [`PropsRef::prom_iter`]. Returning
+/// `ControlFlow::Break` stops the walk early.
+pub fn for_each_device_ref<F>(fetch_prom: bool, mut f: F) -> Result<()>
+where
+    F: FnMut(&DeviceKey, &PropsRef<'_>) -> ControlFlow<()>,
+{
+    let mut prom = prom_for(fetch_prom)?;
+    // The handle is opened up front so that a failure is an error for the
+    // walk rather than silently empty prom properties.
+    let ph = match prom.as_mut().map(Prom::handle) {
+        None => null_mut(),
+        Some(Ok(ph)) => ph,
+        Some(Err(Some(errno))) => {
+            return Err(Error::from_raw_os_error(errno).into())
+        }
+        Some(Err(None)) => return Err(Error::last_os_error().into()),
+    };
+
+    let root_node = snapshot("/", DINFOSUBTREE | DINFOPROP)?;
+    let result =
+        catch_unwind(AssertUnwindSafe(|| visit_refs(root_node, ph, &mut f)));
+    unsafe { di_fini(root_node) };
+
+    match result {
+        Ok(result) => result,
+        Err(payload) => resume_unwind(payload),
+    }
+}
+
+/// Read one property of the first device with the given key from exactly
+/// one property list, e.g. to see what a driver set as opposed to what the
+/// system defaulted. Returns `Ok(None)` if there is no such device, or the
+/// device has no such property on that list.
+pub fn get_prop_from(
+    key: &DeviceKey,
+    name: &str,
+    source: PropSource,
+) -> Result<Option<DiPropValue>> {
+    let mut value = None;
+    for_each_device_ref(false, |k, props| {
+        if k != key {
+            return ControlFlow::Continue(());
+        }
+        value = props.get_from(name, source).map(|v| v.to_value());
+        ControlFlow::Break(())
+    })?;
+    Ok(value)
+}
+
+/// Visit the nodes of a snapshot in the same order as `di_walk_node` with
+/// `DI_WALK_CLDFIRST`: each node, then its children, then its siblings.
+fn visit_refs<F>(
+    root_node: di_node_t,
+    prom: di_prom_handle_t,
+    f: &mut F,
+) -> Result<()>
+where
+    F: FnMut(&DeviceKey, &PropsRef<'_>) -> ControlFlow<()>,
+{
+    let mut stack = vec![root_node];
+    while let Some(node) = stack.pop() {
+        let key = node_key(node)?;
+        let props = PropsRef {
+            node,
+            prom,
+            _snapshot: PhantomData,
+        };
+        if f(&key, &props).is_break() {
+            break;
+        }
+
+        if node != root_node {
+            let sibling = unsafe { di_sibling_node(node) };
+            if !sibling.is_null() {
+                stack.push(sibling);
+            }
+        }
+        let child = unsafe { di_child_node(node) };
+        if !child.is_null() {
+            stack.push(child);
+        }
+    }
+    Ok(())
+}
+
+/// Like [`get_devices`], but also snapshot multipath (MPxIO) information,
+/// so that each client device's `paths` lists the paths to it.
+pub fn get_devices_with_paths(
+    fetch_prom: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    get_devices_with(SnapshotFlags::default() | SnapshotFlags::PATH, fetch_prom)
+}
+
+fn walk(
+    root: &str,
+    flags: c_uint,
+    prom: Option<Prom>,
+    leaves_only: bool,
+    f: &mut NodeFn<'_>,
+) -> Result<()> {
+    let root_node = snapshot(root, flags)?;
 
     let mut ctx = Context {
-        info: BTreeMap::new(),
-        fetch_prom,
+        prom,
+        root: root.to_string(),
+        leaves_only,
+        siblings: SiblingIndexer::default(),
+        error: None,
+        panic: None,
+        f,
     };
 
     unsafe {
@@ -351,22 +1255,451 @@ pub fn get_devices(
         di_fini(root_node);
     };
 
-    Ok(ctx.info)
+    // A panic in the caller's closure can't unwind through libdevinfo, so
+    // it is caught in the walk callback and resumed here.
+    if let Some(payload) = ctx.panic {
+        resume_unwind(payload);
+    }
+
+    match ctx.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Collect the devices bound to the named driver, e.g. `nvme`. This uses
+/// the snapshot's per-driver node list, so only that driver's nodes are
+/// visited.
+pub fn get_devices_by_driver(
+    driver: &str,
+    fetch_prom: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    DeviceQuery::new()
+        .driver(driver)
+        .fetch_prom(fetch_prom)
+        .run()
+}
+
+fn driver_nodes(
+    root_node: di_node_t,
+    driver: &CStr,
+    prom: &mut Option<Prom>,
+    root: &str,
+    leaves_only: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    let mut info = BTreeMap::new();
+
+    let mut node = unsafe { di_drv_first_node(driver.as_ptr(), root_node) };
+    while !node.is_null() {
+        if !leaves_only || is_leaf(node) {
+            let index = sibling_index(node);
+            let (key, dev_info) = read_node(node, index, prom.as_mut(), root)?;
+            info.insert(key, dev_info);
+        }
+        node = unsafe { di_drv_next_node(node) };
+    }
+
+    Ok(info)
+}
+
+/// Look up a single driver instance, e.g. `nvme` instance 3. Returns
+/// `Ok(None)` if the driver has no such instance. Only the driver's nodes
+/// are visited, and only the matching node is read.
+pub fn get_device_by_instance(
+    driver: &str,
+    instance: i32,
+    fetch_prom: bool,
+) -> Result<Option<(DeviceKey, DeviceInfo)>> {
+    let driver = CString::new(driver)?;
+    let root = "/";
+    let mut prom = prom_for(fetch_prom)?;
+    let root_node = snapshot(root, SNAPSHOT_FLAGS)?;
+
+    let mut node = unsafe { di_drv_first_node(driver.as_ptr(), root_node) };
+    while !node.is_null() && unsafe { di_instance(node) } != instance {
+        node = unsafe { di_drv_next_node(node) };
+    }
+
+    let info = if node.is_null() {
+        Ok(None)
+    } else {
+        read_node(node, sibling_index(node), prom.as_mut(), root).map(Some)
+    };
+    unsafe { di_fini(root_node) };
+
+    info
+}
+
+/// Like [`get_devices_by_driver`], but only read the named properties, each
+/// with the type it is expected to have. Properties are looked up by name
+/// rather than by walking every property of every node, and minor nodes and
+/// prom data are not collected. Properties that are absent, or are not of
+/// the given type, are left out of `props`.
+pub fn get_driver_props(
+    driver: &str,
+    props: &[(&str, DiPropType)],
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    let driver = CString::new(driver)?;
+    let props = props
+        .iter()
+        .map(|(name, t)| Ok((CString::new(*name)?, *t)))
+        .collect::<Result<Vec<_>>>()?;
+    let root = "/";
+    let root_node = snapshot(root, SNAPSHOT_FLAGS)?;
+
+    let info = driver_props(root_node, &driver, &props, root);
+    unsafe { di_fini(root_node) };
+
+    info
+}
+
+fn driver_props(
+    root_node: di_node_t,
+    driver: &CStr,
+    props: &[(CString, DiPropType)],
+    root: &str,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    let mut info = BTreeMap::new();
+
+    let mut node = unsafe { di_drv_first_node(driver.as_ptr(), root_node) };
+    while !node.is_null() {
+        let (key, mut dev_info) =
+            read_node_basics(node, sibling_index(node), root)?;
+        for (name, t) in props {
+            if let Some(value) = lookup_prop(node, name, *t) {
+                dev_info
+                    .props
+                    .insert(name.to_string_lossy().into_owned(), value);
+            }
+        }
+        info.insert(key, dev_info);
+        node = unsafe { di_drv_next_node(node) };
+    }
+
+    Ok(info)
+}
+
+/// Look up a single property of `node` by name. Returns `None` if the
+/// property is absent or is not of type `prop_type`.
+fn lookup_prop(
+    node: di_node_t,
+    name: &CStr,
+    prop_type: DiPropType,
+) -> Option<DiPropValue> {
+    let name = name.as_ptr();
+    match prop_type {
+        DiPropType::Boolean => {
+            // Boolean properties have no data, so a lookup of any type
+            // succeeds with a count of zero.
+            let mut data: *mut c_int = null_mut();
+            let count = unsafe {
+                di_prop_lookup_ints(DDI_DEV_T_ANY, node, name, &mut data)
+            };
+            (count == 0).then_some(DiPropValue::Boolean(true))
+        }
+        DiPropType::Int => {
+            let mut data: *mut c_int = null_mut();
+            let count = unsafe {
+                di_prop_lookup_ints(DDI_DEV_T_ANY, node, name, &mut data)
+            };
+            (count > 0).then(|| {
+                let values =
+                    unsafe { slice::from_raw_parts(data, count as usize) };
+                DiPropValue::Ints(Vec::from(values))
+            })
+        }
+        DiPropType::Int64 => {
+            let mut data: *mut i64 = null_mut();
+            let count = unsafe {
+                di_prop_lookup_int64(DDI_DEV_T_ANY, node, name, &mut data)
+            };
+            (count > 0).then(|| {
+                let values =
+                    unsafe { slice::from_raw_parts(data, count as usize) };
+                DiPropValue::Int64s(Vec::from(values))
+            })
+        }
+        DiPropType::String => {
+            let mut data: *mut c_char = null_mut();
+            let count = unsafe {
+                di_prop_lookup_strings(DDI_DEV_T_ANY, node, name, &mut data)
+            };
+            (count > 0).then(|| {
+                DiPropValue::Strings(unsafe { read_strings(data, count) })
+            })
+        }
+        DiPropType::Byte | DiPropType::Unknown | DiPropType::UndefIt => {
+            let mut data: *mut c_uchar = null_mut();
+            let count = unsafe {
+                di_prop_lookup_bytes(DDI_DEV_T_ANY, node, name, &mut data)
+            };
+            (count > 0).then(|| {
+                let values =
+                    unsafe { slice::from_raw_parts(data, count as usize) };
+                DiPropValue::Bytes(Vec::from(values))
+            })
+        }
+    }
+}
+
+/// Read the raw parent and driver private data of the node at the physical
+/// path `path`, `parent_len` and `driver_len` bytes respectively. Either is
+/// `None` if the snapshot has no such data for the node. The kernel only
+/// copies private data whose format it knows, and doing so typically
+/// requires root privilege.
+///
+/// # Safety
+///
+/// Private data carries no length or type information. The caller must
+/// know the layout of the structures the node's parent nexus and driver
+/// keep there, and the lengths given must not exceed their sizes, or this
+/// reads past the end of them.
+#[cfg(feature = "private-data")]
+pub unsafe fn raw_private_data(
+    path: &str,
+    parent_len: usize,
+    driver_len: usize,
+) -> Result<PrivateData> {
+    let root_node = snapshot(path, DINFOCPYONE | DINFOPRIVDATA)?;
+
+    let read = |data: *mut c_void, len: usize| {
+        if data.is_null() {
+            None
+        } else {
+            Some(slice::from_raw_parts(data as *const u8, len).to_vec())
+        }
+    };
+    let data = PrivateData {
+        parent: read(di_parent_private_data(root_node), parent_len),
+        driver: read(di_driver_private_data(root_node), driver_len),
+    };
+    di_fini(root_node);
+
+    Ok(data)
+}
+
+/// A node of a snapshot that may be handed to other threads. Snapshots are
+/// read-only copies of the device tree, so their nodes can be read
+/// concurrently for as long as the snapshot is held.
+#[cfg(feature = "parallel")]
+#[derive(Clone, Copy)]
+struct SnapshotNode(di_node_t);
+
+#[cfg(feature = "parallel")]
+unsafe impl Send for SnapshotNode {}
+#[cfg(feature = "parallel")]
+unsafe impl Sync for SnapshotNode {}
+
+/// Like [`get_devices`], but extract the properties of each node in
+/// parallel. This pays off on systems with many devices. Prom properties
+/// are still read one node at a time, as the prom handle serializes access
+/// to `/dev/openprom` anyway.
+#[cfg(feature = "parallel")]
+pub fn get_devices_parallel(
+    fetch_prom: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    use rayon::prelude::*;
+
+    let root = "/";
+    let mut prom = prom_for(fetch_prom)?;
+    let root_node = snapshot(root, SNAPSHOT_FLAGS)?;
+
+    let mut nodes = Vec::new();
+    collect_nodes(root_node, sibling_index(root_node), &mut nodes);
+
+    let devices = nodes
+        .par_iter()
+        .map(|(node, index)| {
+            let (key, info) = read_node(node.0, *index, None, root)?;
+            Ok((*node, key, info))
+        })
+        .collect::<Result<Vec<_>>>();
+
+    let devices = devices.map(|devices| {
+        devices
+            .into_iter()
+            .map(|(node, key, mut info)| {
+                if let Some(prom) = prom.as_mut() {
+                    read_prom_props(node.0, &key, prom, &mut info);
+                }
+                (key, info)
+            })
+            .collect()
+    });
+    unsafe { di_fini(root_node) };
+
+    devices
+}
+
+#[cfg(feature = "parallel")]
+fn collect_nodes(
+    node: di_node_t,
+    index: usize,
+    nodes: &mut Vec<(SnapshotNode, usize)>,
+) {
+    nodes.push((SnapshotNode(node), index));
+    let mut child = unsafe { di_child_node(node) };
+    let mut child_index = 0;
+    while !child.is_null() {
+        collect_nodes(child, child_index, nodes);
+        child = unsafe { di_sibling_node(child) };
+        child_index += 1;
+    }
+}
+
+/// Collect the device hierarchy, with each node owning its children.
+pub fn get_device_tree(fetch_prom: bool) -> Result<DeviceTree> {
+    let root = "/";
+    let mut prom = prom_for(fetch_prom)?;
+    let root_node = snapshot(root, SNAPSHOT_FLAGS)?;
+
+    let index = sibling_index(root_node);
+    let tree = build_tree(root_node, index, &mut prom, root, None);
+    unsafe { di_fini(root_node) };
+
+    Ok(DeviceTree { root: tree? })
+}
+
+/// Collect both the device hierarchy and the keyed map [`get_devices`]
+/// returns, from a single walk. The map holds copies of the tree's nodes;
+/// as with [`get_devices`], a later node replaces an earlier one with the
+/// same key.
+pub fn get_both(
+    fetch_prom: bool,
+) -> Result<(DeviceTree, BTreeMap<DeviceKey, DeviceInfo>)> {
+    let root = "/";
+    let mut prom = prom_for(fetch_prom)?;
+    let root_node = snapshot(root, SNAPSHOT_FLAGS)?;
+
+    let mut map = BTreeMap::new();
+    let index = sibling_index(root_node);
+    let tree = build_tree(root_node, index, &mut prom, root, Some(&mut map));
+    unsafe { di_fini(root_node) };
+
+    Ok((DeviceTree { root: tree? }, map))
+}
+
+fn snapshot(root: &str, flags: c_uint) -> Result<di_node_t> {
+    let path = CString::new(root)?;
+    let root_node =
+        unsafe { di_init(path.as_c_str().as_ptr() as *const c_char, flags) };
+    if root_node.is_null() {
+        return Err(snapshot_error(Error::last_os_error(), root));
+    }
+    Ok(root_node)
+}
+
+/// Classify why `di_init` failed for `root`.
+pub(crate) fn snapshot_error(e: Error, root: &str) -> DevInfoError {
+    match e.raw_os_error() {
+        Some(EPERM) | Some(EACCES) => DevInfoError::PermissionDenied,
+        Some(ENOMEM) => DevInfoError::OutOfMemory,
+        Some(ENOENT) | Some(ENXIO) => DevInfoError::NotFound(root.to_string()),
+        _ => DevInfoError::Io(e),
+    }
+}
+
+fn build_tree(
+    node: di_node_t,
+    index: usize,
+    prom: &mut Option<Prom>,
+    root: &str,
+    mut map: Option<&mut BTreeMap<DeviceKey, DeviceInfo>>,
+) -> Result<DeviceNode> {
+    let (key, info) = read_node(node, index, prom.as_mut(), root)?;
+    if let Some(map) = map.as_deref_mut() {
+        map.insert(key.clone(), info.clone());
+    }
+
+    let mut children = Vec::new();
+    let mut child = unsafe { di_child_node(node) };
+    while !child.is_null() {
+        let index = children.len();
+        let map = map.as_deref_mut();
+        children.push(build_tree(child, index, prom, root, map)?);
+        child = unsafe { di_sibling_node(child) };
+    }
+
+    Ok(DeviceNode {
+        key,
+        info,
+        children,
+    })
+}
+
+/// Check whether prom properties can be read, i.e. whether `/dev/openprom`
+/// can be opened. This typically requires root privilege, so callers that
+/// want prom data can check up front rather than partway through a walk.
+pub fn prom_available() -> bool {
+    std::fs::File::open("/dev/openprom").is_ok()
+}
+
+/// Check whether walks can fetch prom properties. Walks asked to fetch
+/// them fail with [`DevInfoError::PermissionDenied`] when this is false.
+/// Root always can; other users can when granted access to
+/// `/dev/openprom`, as [`prom_available`] checks.
+pub fn can_fetch_prom() -> bool {
+    let euid = unsafe { geteuid() };
+    euid == 0 || prom_available()
 }
 
-fn print_err(msg: String) {
-    let err = std::io::Error::last_os_error();
-    println!("{}: {}", msg, err);
+/// Record a failed read of part of a node, along with the errno the failing
+/// call left behind.
+fn add_warning(
+    info: &mut DeviceInfo,
+    key: &DeviceKey,
+    prop: Option<&str>,
+    message: &str,
+) {
+    info.warnings.push(DevInfoWarning {
+        node: key.clone(),
+        prop: prop.map(str::to_owned),
+        errno: Error::last_os_error().raw_os_error(),
+        message: message.to_owned(),
+    });
+}
+
+fn is_leaf(node: di_node_t) -> bool {
+    unsafe { di_child_node(node) }.is_null()
 }
 
 extern "C" fn node_info(node: di_node_t, arg: *mut c_void) -> c_int {
     let ctx = unsafe { &mut *(arg as *mut Context) };
 
-    let cs = unsafe { CStr::from_ptr(di_node_name(node)) };
-    let node_name = cs.to_str().unwrap().to_owned();
-    let mut unit_address = None;
+    // Every node is indexed, including those skipped below, to keep track
+    // of where the walk is.
+    let index = ctx.siblings.index(node);
+    if ctx.leaves_only && !is_leaf(node) {
+        return DI_WALK_CONTINUE;
+    }
 
-    let mut info = DeviceInfo::new();
+    let prom = ctx.prom.as_mut();
+    let (key, info) = match read_node(node, index, prom, &ctx.root) {
+        Ok(x) => x,
+        Err(e) => {
+            ctx.error = Some(e);
+            return DI_WALK_TERMINATE;
+        }
+    };
+
+    let f = &mut ctx.f;
+    match catch_unwind(AssertUnwindSafe(|| f(key, info))) {
+        Ok(ControlFlow::Continue(())) => DI_WALK_CONTINUE,
+        Ok(ControlFlow::Break(())) => DI_WALK_TERMINATE,
+        Err(payload) => {
+            ctx.panic = Some(payload);
+            DI_WALK_TERMINATE
+        }
+    }
+}
+
+fn read_node(
+    node: di_node_t,
+    sibling_index: usize,
+    prom: Option<&mut Prom>,
+    root: &str,
+) -> Result<(DeviceKey, DeviceInfo)> {
+    let (key, mut info) = read_node_basics(node, sibling_index, root)?;
 
     let mut prop: di_prop_t = null_mut();
     loop {
@@ -376,132 +1709,494 @@ extern "C" fn node_info(node: di_node_t, arg: *mut c_void) -> c_int {
         }
 
         let cs = unsafe { CStr::from_ptr(di_prop_name(prop)) };
-        let prop_name = cs.to_str().unwrap();
+        let prop_name = cs.to_string_lossy();
 
         let prop_type = unsafe { di_prop_type(prop) };
-        match DiPropType::try_from(prop_type) {
+        let value = match DiPropType::try_from(prop_type) {
             Ok(t) => match t {
                 DiPropType::Boolean => {
                     //existence implies true
-                    info.props.insert(
-                        prop_name.to_string(),
-                        DiPropValue::Boolean(true),
-                    );
+                    DiPropValue::Boolean(true)
                 }
                 DiPropType::Int => {
                     let mut data: *mut i32 = null_mut();
                     let count = unsafe { di_prop_ints(prop, &mut data) };
                     if count < 0 {
-                        print_err(format!("{} failed to get ints", prop_name));
+                        add_warning(
+                            &mut info,
+                            &key,
+                            Some(&prop_name),
+                            "failed to get ints",
+                        );
                         continue;
                     }
-                    let values: &[i32] = unsafe {
-                        slice::from_raw_parts_mut(data, count as usize)
-                    };
-
-                    info.props.insert(
-                        prop_name.to_string(),
-                        DiPropValue::Ints(Vec::from(values)),
-                    );
+                    let values: &[i32] = unsafe { borrow(data, count) };
+                    DiPropValue::Ints(Vec::from(values))
                 }
                 DiPropType::Int64 => {
                     let mut data: *mut i64 = null_mut();
                     let count = unsafe { di_prop_int64(prop, &mut data) };
                     if count < 0 {
-                        print_err(format!(
-                            "{} failed to get int64s",
-                            prop_name
-                        ));
+                        add_warning(
+                            &mut info,
+                            &key,
+                            Some(&prop_name),
+                            "failed to get int64s",
+                        );
                         continue;
                     }
-                    let values: &[i64] = unsafe {
-                        slice::from_raw_parts_mut(data, count as usize)
-                    };
-
-                    info.props.insert(
-                        prop_name.to_string(),
-                        DiPropValue::Int64s(Vec::from(values)),
-                    );
+                    let values: &[i64] = unsafe { borrow(data, count) };
+                    DiPropValue::Int64s(Vec::from(values))
                 }
                 DiPropType::String => {
                     let mut data: *mut c_char = null_mut();
                     let count = unsafe { di_prop_strings(prop, &mut data) };
                     if count < 0 {
-                        print_err(format!(
-                            "{} failed to get strings",
-                            prop_name
-                        ));
+                        add_warning(
+                            &mut info,
+                            &key,
+                            Some(&prop_name),
+                            "failed to get strings",
+                        );
                         continue;
                     }
 
-                    let bytes: &mut [u8] = unsafe {
-                        slice::from_raw_parts_mut(
-                            data as *mut u8,
-                            count as usize,
-                        )
-                    };
-
-                    let concat_str =
-                        unsafe { std::str::from_utf8_unchecked_mut(bytes) };
-                    let values: Vec<&str> =
-                        concat_str.split_terminator('\0').collect();
-
-                    let mut vals = Vec::new();
-                    for x in &values {
-                        vals.push(x.to_string());
-                    }
-                    if prop_name == "unit-address" && !vals.is_empty() {
-                        unit_address = Some(vals[0].clone());
+                    // `count` is the number of strings, not their length in
+                    // bytes, and drivers are free to publish non-UTF-8 data.
+                    DiPropValue::Strings(unsafe { read_strings(data, count) })
+                }
+                // Properties of unknown or undefined type still carry their
+                // raw payload, which is better reported as bytes than
+                // dropped.
+                DiPropType::Byte
+                | DiPropType::Unknown
+                | DiPropType::UndefIt => {
+                    let mut data: *mut c_uchar = null_mut();
+                    let count = unsafe { di_prop_bytes(prop, &mut data) };
+                    if count < 0 {
+                        add_warning(
+                            &mut info,
+                            &key,
+                            Some(&prop_name),
+                            "failed to get bytes",
+                        );
+                        continue;
                     }
-                    info.props.insert(
-                        prop_name.to_string(),
-                        DiPropValue::Strings(vals),
-                    );
+                    let values: &[u8] = unsafe { borrow(data, count) };
+                    DiPropValue::Bytes(Vec::from(values))
                 }
-                _ => {}
             },
             Err(_) => continue,
         };
+
+        // libdevinfo has no accessor for the list a property is on, so read
+        // it straight from the snapshot.
+        let source = PropSource::try_from(unsafe { (*prop).prop_list }).ok();
+
+        // Properties are returned driver list first, which is also the
+        // precedence the DDI gives them, so the first value seen wins and
+        // any later ones of the same name are kept aside.
+        if info.props.contains_key(prop_name.as_ref()) {
+            info.shadowed_props.push(ShadowedProp {
+                name: prop_name.to_string(),
+                source,
+                value,
+            });
+            continue;
+        }
+        if let Some(source) = source {
+            info.prop_sources.insert(prop_name.to_string(), source);
+        }
+        info.props.insert(prop_name.to_string(), value);
     }
 
-    if ctx.fetch_prom {
-        let ph = unsafe { di_prom_init() };
-        if ph.is_null() {
-            print_err("di_promi_init".to_string());
-            return DI_WALK_CONTINUE;
+    read_minors(node, &key, &mut info);
+    read_paths(node, &mut info);
+    read_links(node, &key, &mut info);
+    read_hotplug(node, &mut info);
+
+    if let Some(prom) = prom {
+        if !prom.skip.as_ref().is_some_and(|skip| skip(&key)) {
+            read_prom_props(node, &key, prom, &mut info);
         }
+    }
 
-        let mut prom_prop: di_prom_prop_t = null_mut();
+    Ok((key, info))
+}
+
+fn read_node_basics(
+    node: di_node_t,
+    sibling_index: usize,
+    root: &str,
+) -> Result<(DeviceKey, DeviceInfo)> {
+    let key = node_key(node)?;
+
+    let mut info = DeviceInfo::new();
+    info.node_name = key.node_name.clone();
+    info.snapshot_root = Some(root.to_string());
+    info.instance = unsafe { di_instance(node) };
+    info.sibling_index = sibling_index;
+    info.nodeid = match unsafe { di_nodeid(node) } {
+        DI_PSEUDO_NODEID => NodeId::Pseudo,
+        DI_SID_NODEID => NodeId::Persistent,
+        id => NodeId::Prom(id),
+    };
+    info.node_state = NodeState::try_from(unsafe { di_node_state(node) })
+        .unwrap_or(NodeState::Invalid);
+
+    let name = unsafe { di_binding_name(node) };
+    if !name.is_null() {
+        let cs = unsafe { CStr::from_ptr(name) };
+        info.binding_name = Some(cs.to_string_lossy().into_owned());
+    }
+
+    let name = unsafe { di_driver_name(node) };
+    if !name.is_null() {
+        let cs = unsafe { CStr::from_ptr(name) };
+        info.driver_name = Some(cs.to_string_lossy().into_owned());
+    }
+    let major = unsafe { di_driver_major(node) };
+    if major >= 0 {
+        info.driver_major = Some(major);
+    }
+    info.driver_ops =
+        DriverOps::from_bits_retain(unsafe { di_driver_ops(node) });
+
+    // libdevinfo has no accessor for the node class, so read it straight
+    // from the snapshot.
+    info.node_class = NodeClass::try_from(unsafe { (*node).node_class })
+        .unwrap_or(NodeClass::Pseudo);
+    info.multipath_role =
+        MultipathRole::try_from(unsafe { (*node).multipath_component })
+            .unwrap_or(MultipathRole::None);
+    info.attributes =
+        NodeAttributes::from_bits_retain(unsafe { (*node).attributes } as u32);
+    info.flags = NodeFlags::from_bits_retain(unsafe { (*node).flags });
+
+    let mut names: *mut c_char = null_mut();
+    let count = unsafe { di_compatible_names(node, &mut names) };
+    if count > 0 {
+        info.compatible = unsafe { read_strings(names, count) };
+    }
+
+    info.devfs_path = devfs_path(node);
+
+    let devid = unsafe { di_devid(node) };
+    if !devid.is_null() {
+        let s = unsafe { devid_str_encode(devid, null_mut()) };
+        info.devid =
+            OwnedStr::new(s, devid_str_free).map(|s| s.to_string_lossy());
+    }
+
+    Ok((key, info))
+}
+
+/// Sibling indices for the nodes of a `DI_WALK_CLDFIRST` walk, worked out
+/// as the walk goes rather than by rescanning each parent's children. The
+/// path holds the nodes from the walk's first node to the latest, with
+/// their indices: a node either is the first child of the latest, or
+/// follows one of the nodes on the path.
+#[derive(Default)]
+struct SiblingIndexer {
+    path: Vec<(di_node_t, usize)>,
+}
+
+impl SiblingIndexer {
+    fn index(&mut self, node: di_node_t) -> usize {
+        let parent = unsafe { di_parent_node(node) };
+        let mut index = None;
+        while let Some((last, last_index)) = self.path.last().copied() {
+            if last == parent {
+                index = Some(0);
+                break;
+            }
+            self.path.pop();
+            if unsafe { di_sibling_node(last) } == node {
+                index = Some(last_index + 1);
+                break;
+            }
+        }
+        // The walk's first node needn't be its parent's first child.
+        let index = index.unwrap_or_else(|| sibling_index(node));
+        self.path.push((node, index));
+        index
+    }
+}
+
+/// Count the siblings before `node`. libdevinfo only links siblings
+/// forwards, so this starts from the parent's first child.
+fn sibling_index(node: di_node_t) -> usize {
+    let parent = unsafe { di_parent_node(node) };
+    if parent.is_null() {
+        return 0;
+    }
+
+    let mut index = 0;
+    let mut sibling = unsafe { di_child_node(parent) };
+    while !sibling.is_null() && sibling != node {
+        index += 1;
+        sibling = unsafe { di_sibling_node(sibling) };
+    }
+    index
+}
+
+fn node_key(node: di_node_t) -> Result<DeviceKey> {
+    let name = unsafe { di_node_name(node) };
+    if name.is_null() {
+        return Err(DevInfoError::NullPointer("di_node_name"));
+    }
+    let cs = unsafe { CStr::from_ptr(name) };
+    let node_name = cs.to_string_lossy().into_owned();
+
+    // The bus address is kept apart from the node name in the snapshot, so
+    // use it directly rather than splitting the name or trusting a
+    // `unit-address` property to be present.
+    let addr = unsafe { di_bus_addr(node) };
+    let unit_address = if addr.is_null() {
+        None
+    } else {
+        let cs = unsafe { CStr::from_ptr(addr) };
+        Some(cs.to_string_lossy().into_owned()).filter(|a| !a.is_empty())
+    };
+    Ok(DeviceKey {
+        node_name,
+        unit_address,
+    })
+}
+
+/// Read `count` consecutive nul-terminated strings starting at `data`, which
+/// is how libdevinfo returns string arrays.
+pub(crate) unsafe fn read_strings(
+    data: *const c_char,
+    count: c_int,
+) -> Vec<String> {
+    let mut result = Vec::with_capacity(count as usize);
+    let mut p = data;
+    for _ in 0..count {
+        let cs = CStr::from_ptr(p);
+        result.push(cs.to_string_lossy().into_owned());
+        p = p.add(cs.to_bytes_with_nul().len());
+    }
+    result
+}
+
+fn read_minors(node: di_node_t, key: &DeviceKey, info: &mut DeviceInfo) {
+    let mut minor: di_minor_t = null_mut();
+    loop {
+        minor = unsafe { di_minor_next(node, minor) };
+        if minor.is_null() {
+            break;
+        }
+
+        match read_minor(minor) {
+            Ok(m) => info.minors.push(m),
+            Err(message) => info.warnings.push(DevInfoWarning {
+                node: key.clone(),
+                prop: None,
+                errno: None,
+                message,
+            }),
+        }
+    }
+}
+
+/// Read a single minor node. Minors of a spec type other than block or
+/// character can't be represented, so they yield a warning instead.
+fn read_minor(minor: di_minor_t) -> std::result::Result<MinorNode, String> {
+    let cs = unsafe { CStr::from_ptr(di_minor_name(minor)) };
+    let name = cs.to_string_lossy().into_owned();
+
+    let spec_type = match unsafe { di_minor_spectype(minor) } {
+        S_IFBLK => SpecType::Block,
+        S_IFCHR => SpecType::Char,
+        x => return Err(format!("{}: unknown spec type {:#x}", name, x)),
+    };
+
+    let nt = unsafe { di_minor_nodetype(minor) };
+    let node_type = if nt.is_null() {
+        None
+    } else {
+        let cs = unsafe { CStr::from_ptr(nt) };
+        Some(cs.to_string_lossy().into_owned())
+    };
+
+    let minor_path = OwnedStr::new(
+        unsafe { di_devfs_minor_path(minor) },
+        di_devfs_path_free,
+    )
+    .map(|s| s.to_string_lossy());
+
+    Ok(MinorNode {
+        name,
+        node_type,
+        spec_type,
+        minor_path,
+    })
+}
+
+fn read_paths(node: di_node_t, info: &mut DeviceInfo) {
+    let mut path: di_path_t = null_mut();
+    loop {
+        path = unsafe { di_path_client_next_path(node, path) };
+        if path.is_null() {
+            break;
+        }
+
+        let addr = unsafe { di_path_bus_addr(path) };
+        let bus_address = if addr.is_null() {
+            None
+        } else {
+            let cs = unsafe { CStr::from_ptr(addr) };
+            Some(cs.to_string_lossy().into_owned())
+        };
+
+        let state = PathState::try_from(unsafe { di_path_state(path) })
+            .unwrap_or(PathState::Unknown);
+
+        let phci_node = unsafe { di_path_phci_node(path) };
+        let phci = if phci_node.is_null() {
+            None
+        } else {
+            devfs_path(phci_node)
+        };
+
+        info.paths.push(PathInfo {
+            bus_address,
+            state,
+            phci,
+        });
+    }
+}
+
+fn read_links(node: di_node_t, key: &DeviceKey, info: &mut DeviceInfo) {
+    for endpoint in [DI_LINK_SRC, DI_LINK_TGT] {
+        let mut link: di_link_t = null_mut();
         loop {
-            prom_prop = unsafe { di_prom_prop_next(ph, node, prom_prop) };
-            if prom_prop.is_null() {
+            link = unsafe { di_link_next_by_node(node, link, endpoint) };
+            if link.is_null() {
                 break;
             }
 
-            let cs = unsafe { CStr::from_ptr(di_prom_prop_name(prom_prop)) };
-            let prop_name = cs.to_str().unwrap();
+            let spec_type = match unsafe { di_link_spectype(link) } {
+                S_IFBLK => SpecType::Block,
+                S_IFCHR => SpecType::Char,
+                x => {
+                    info.warnings.push(DevInfoWarning {
+                        node: key.clone(),
+                        prop: None,
+                        errno: None,
+                        message: format!("link: unknown spec type {:#x}", x),
+                    });
+                    continue;
+                }
+            };
+
+            info.links.push(DeviceLink {
+                source: link_end(link, DI_LINK_SRC),
+                target: link_end(link, DI_LINK_TGT),
+                spec_type,
+            });
+        }
+    }
+}
+
+fn link_end(link: di_link_t, endpoint: c_uint) -> LinkEnd {
+    let lnode = unsafe { di_link_to_lnode(link, endpoint) };
 
+    let name = unsafe { di_lnode_name(lnode) };
+    let name = if name.is_null() {
+        String::new()
+    } else {
+        let cs = unsafe { CStr::from_ptr(name) };
+        cs.to_string_lossy().into_owned()
+    };
+
+    let node = unsafe { di_lnode_devinfo(lnode) };
+    let node = if node.is_null() {
+        None
+    } else {
+        node_key(node).ok()
+    };
+
+    LinkEnd { name, node }
+}
+
+fn read_hotplug(node: di_node_t, info: &mut DeviceInfo) {
+    let mut hp: di_hp_t = null_mut();
+    loop {
+        hp = unsafe { di_hp_next(node, hp) };
+        if hp.is_null() {
+            break;
+        }
+
+        let name = unsafe { di_hp_name(hp) };
+        let name = if name.is_null() {
+            String::new()
+        } else {
+            let cs = unsafe { CStr::from_ptr(name) };
+            cs.to_string_lossy().into_owned()
+        };
+        let state = HotplugState::try_from(unsafe { di_hp_state(hp) })
+            .unwrap_or(HotplugState::Unknown);
+
+        info.hotplug.push(HotplugConnector { name, state });
+    }
+}
+
+fn read_prom_props(
+    node: di_node_t,
+    key: &DeviceKey,
+    prom: &mut Prom,
+    info: &mut DeviceInfo,
+) {
+    let ph = match prom.handle() {
+        Ok(ph) => ph,
+        Err(errno) => {
+            info.warnings.push(DevInfoWarning {
+                node: key.clone(),
+                prop: None,
+                errno,
+                message: "di_prom_init failed".to_owned(),
+            });
+            return;
+        }
+    };
+
+    if let Some(only) = &prom.only {
+        for name in only {
             let mut data: *mut c_uchar = null_mut();
-            let len = unsafe { di_prom_prop_data(prom_prop, &mut data) };
+            let len = unsafe {
+                di_prom_prop_lookup_bytes(ph, node, name.as_ptr(), &mut data)
+            };
+            // A node without the property is not an error.
             if len < 0 {
-                print_err(format!("{} get bytes", prop_name));
                 continue;
             }
-            let bytes =
-                unsafe { slice::from_raw_parts_mut(data, len as usize) };
+            let bytes = unsafe { borrow(data, len) };
             info.prom_props
-                .insert(prop_name.to_string(), Vec::from(bytes));
+                .insert(name.to_string_lossy().into_owned(), Vec::from(bytes));
         }
-        unsafe { di_prom_fini(ph) };
+        return;
     }
 
-    ctx.info.insert(
-        DeviceKey {
-            node_name,
-            unit_address,
-        },
-        info,
-    );
+    let mut prom_prop: di_prom_prop_t = null_mut();
+    loop {
+        prom_prop = unsafe { di_prom_prop_next(ph, node, prom_prop) };
+        if prom_prop.is_null() {
+            break;
+        }
 
-    DI_WALK_CONTINUE
+        let cs = unsafe { CStr::from_ptr(di_prom_prop_name(prom_prop)) };
+        let prop_name = cs.to_string_lossy();
+
+        let mut data: *mut c_uchar = null_mut();
+        let len = unsafe { di_prom_prop_data(prom_prop, &mut data) };
+        if len < 0 {
+            add_warning(info, key, Some(&prop_name), "failed to get bytes");
+            continue;
+        }
+        let bytes = unsafe { borrow(data, len) };
+        info.prom_props
+            .insert(prop_name.to_string(), Vec::from(bytes));
+    }
 }
@@ -4,11 +4,15 @@
 
 // Copyright 2022 Oxide Computer Company
 
-use crate::sys::DeviceKey;
-use std::io::Result;
+use crate::{DevInfoError, DeviceInfo, DeviceKey, DiPropValue, Mismatch};
+use std::collections::BTreeMap;
+
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+type Result<T> = std::result::Result<T, DevInfoError>;
 
 /// Assert that we can find a CPU. Should work on any platform.
 #[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
 fn find_cpu() -> Result<()> {
     let devs = crate::get_devices(false)?;
     let cpu = devs.get(&DeviceKey {
@@ -28,3 +32,1471 @@ fn find_cpu() -> Result<()> {
 
     Ok(())
 }
+
+/// A driver query finds the same nodes as walking the whole tree and
+/// keeping that driver's, and a query without minor data collects none.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn query_cpus() -> Result<()> {
+    use crate::{DeviceQuery, SnapshotFlags};
+
+    let cpus = DeviceQuery::new().driver("cpu").run()?;
+    assert!(!cpus.is_empty());
+    let mut all = crate::get_devices(false)?;
+    all.retain(|_, dev| dev.driver_name.as_deref() == Some("cpu"));
+    assert_eq!(
+        cpus.keys().collect::<Vec<_>>(),
+        all.keys().collect::<Vec<_>>()
+    );
+
+    let bare = DeviceQuery::new()
+        .driver("cpu")
+        .flags(SnapshotFlags::empty())
+        .run()?;
+    assert_eq!(bare.len(), cpus.len());
+    assert!(bare.values().all(|dev| dev.minors.is_empty()));
+
+    let rooted = DeviceQuery::new()
+        .root("/")
+        .driver("cpu")
+        .fetch_prom(false)
+        .flags(SnapshotFlags::MINOR)
+        .run()?;
+    assert_eq!(rooted.len(), cpus.len());
+    assert!(rooted.values().all(|dev| !dev.props.is_empty()));
+
+    Ok(())
+}
+
+/// Assert that a CPU with instance number 0 exists.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn cpu_instance() -> Result<()> {
+    let devs = crate::get_devices(false)?;
+    let found = devs
+        .iter()
+        .any(|(key, info)| key.node_name == "cpu" && info.instance == 0);
+    assert!(found);
+
+    Ok(())
+}
+
+/// Assert that a driver instance found by a full walk can be looked up
+/// directly, and that a nonexistent instance is not found.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn lookup_instance() -> Result<()> {
+    let devs = crate::get_devices(false)?;
+    let (key, info) = devs
+        .iter()
+        .find(|(_, info)| info.driver_name.is_some() && info.instance >= 0)
+        .expect("no device with a bound driver");
+    let driver = info.driver_name.as_deref().unwrap();
+
+    let found = crate::get_device_by_instance(driver, info.instance, false)?;
+    assert_eq!(found.as_ref().map(|(k, _)| k), Some(key));
+    assert!(crate::get_device_by_instance(driver, i32::MAX, false)?.is_none());
+
+    Ok(())
+}
+
+/// Assert that the PROM tree can be navigated directly, when it can be
+/// opened at all.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn prom_tree() -> Result<()> {
+    if !crate::prom_available() {
+        return Ok(());
+    }
+
+    let mut prom = crate::PromTree::open()?;
+    assert!(!prom.version()?.is_empty());
+
+    let root = prom.root()?.expect("no prom root node");
+    let name = prom.next_property(None)?.expect("root has no properties");
+    assert!(prom.property(&name)?.is_some());
+    assert!(prom.property("no-such-property")?.is_none());
+
+    let mut child = prom.first_child(root)?;
+    while let Some(node) = child {
+        child = prom.next_sibling(node)?;
+    }
+
+    Ok(())
+}
+
+/// Assert that an allowlist of prom properties limits what is fetched.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn only_prom_props() -> Result<()> {
+    if !crate::can_fetch_prom() {
+        return Ok(());
+    }
+
+    let names = ["name", "reg"];
+    let devices = crate::DeviceQuery::new().only_prom_props(&names).run()?;
+    assert!(devices.values().any(|d| !d.prom_props.is_empty()));
+    for dev in devices.values() {
+        assert!(dev.prom_props.keys().all(|k| names.contains(&k.as_str())));
+    }
+
+    Ok(())
+}
+
+/// Assert that skipped devices are still collected, without prom
+/// properties.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn prom_skip_predicate() -> Result<()> {
+    if !crate::can_fetch_prom() {
+        return Ok(());
+    }
+
+    let all = crate::DeviceQuery::new()
+        .fetch_prom(true)
+        .prom_skip_predicate(|_| true)
+        .run()?;
+    assert!(!all.is_empty());
+    assert!(all.values().all(|d| d.prom_props.is_empty()));
+
+    Ok(())
+}
+
+/// Assert that the combined walk's tree and map agree with each other and
+/// with a plain walk.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn tree_and_map() -> Result<()> {
+    let (tree, map) = crate::get_both(false)?;
+    assert_eq!(
+        map.keys().collect::<Vec<_>>(),
+        crate::get_devices(false)?.keys().collect::<Vec<_>>()
+    );
+
+    let mut stack = vec![&tree.root];
+    while let Some(node) = stack.pop() {
+        assert!(map.contains_key(&node.key));
+        stack.extend(&node.children);
+    }
+
+    Ok(())
+}
+
+/// Assert that reading a property from its own list gives the value the
+/// merged walk reported.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn prop_from_list() -> Result<()> {
+    // `get_prop_from` reads the first device with a key, as a streaming
+    // walk sees it.
+    let mut first = None;
+    crate::for_each_device(false, |key, dev| {
+        if dev.prop_sources.is_empty() {
+            return std::ops::ControlFlow::Continue(());
+        }
+        first = Some((key.clone(), dev.clone()));
+        std::ops::ControlFlow::Break(())
+    })?;
+    let (key, dev) = first.expect("no device has properties");
+
+    for (name, source) in &dev.prop_sources {
+        let value = crate::get_prop_from(&key, name, *source)?;
+        assert_eq!(value.as_ref(), dev.prop_from(name, *source));
+    }
+
+    Ok(())
+}
+
+/// Assert that a leaves-only query collects exactly the devices that have
+/// no children in the full tree.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn leaves_only() -> Result<()> {
+    let tree = crate::get_device_tree(false)?;
+    let mut leaves = Vec::new();
+    let mut stack = vec![&tree.root];
+    while let Some(node) = stack.pop() {
+        if !node.has_children() {
+            leaves.push(node.key.clone());
+        }
+        stack.extend(&node.children);
+    }
+    leaves.sort();
+    leaves.dedup();
+
+    let devices = crate::DeviceQuery::new().leaves_only(true).run()?;
+    assert!(!devices.is_empty());
+    assert_eq!(devices.keys().cloned().collect::<Vec<_>>(), leaves);
+
+    Ok(())
+}
+
+/// Assert that breaking out of a streaming walk stops it.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn stream_stops_early() -> Result<()> {
+    let mut seen = 0;
+    crate::for_each_device(false, |_, _| {
+        seen += 1;
+        std::ops::ControlFlow::Break(())
+    })?;
+    assert_eq!(seen, 1);
+
+    Ok(())
+}
+
+/// Assert that counting visits at least every device a full walk collects.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn count_matches_walk() -> Result<()> {
+    let count = crate::count_devices(false)?;
+    let devs = crate::get_devices(false)?;
+    assert!(!devs.is_empty());
+    assert!(count >= devs.len());
+
+    Ok(())
+}
+
+/// Assert that walking minors by node type finds the same minors as a full
+/// walk.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn walk_block_minors() -> Result<()> {
+    let mut walked = Vec::new();
+    crate::walk_minors(Some("ddi_block"), |key, minor| {
+        let node_type = minor.node_type.as_deref().unwrap_or_default();
+        assert!(node_type.starts_with("ddi_block"));
+        let suffix = format!(":{}", minor.name);
+        assert!(minor
+            .minor_path
+            .as_deref()
+            .is_some_and(|path| path.ends_with(&suffix)));
+        walked.push((key.clone(), minor.name.clone()));
+        std::ops::ControlFlow::Continue(())
+    })?;
+
+    let devices = crate::get_devices(false)?;
+    for (key, name) in &walked {
+        assert!(devices[key].minors.iter().any(|m| &m.name == name));
+    }
+
+    Ok(())
+}
+
+/// Assert that each node's sibling index is its position among its
+/// parent's children.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn sibling_indices() -> Result<()> {
+    fn check(node: &crate::DeviceNode) {
+        for (i, child) in node.children.iter().enumerate() {
+            assert_eq!(child.info.sibling_index, i);
+            check(child);
+        }
+    }
+
+    let tree = crate::get_device_tree(false)?;
+    assert_eq!(tree.root.info.sibling_index, 0);
+    check(&tree.root);
+
+    Ok(())
+}
+
+/// Assert that the device tree contains a CPU below the root.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn tree_has_cpu() -> Result<()> {
+    fn find(node: &crate::DeviceNode, name: &str) -> bool {
+        node.key.node_name == name
+            || node.children.iter().any(|c| find(c, name))
+    }
+
+    let tree = crate::get_device_tree(false)?;
+    assert!(!tree.root.children.is_empty());
+    assert!(find(&tree.root, "cpu"));
+
+    Ok(())
+}
+
+/// PCI nexus nodes report their bus address as the unit address.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn pci_bus_address() -> Result<()> {
+    let devs = crate::get_devices(false)?;
+    assert!(devs.contains_key(&DeviceKey::parse("pci@0,0")));
+    Ok(())
+}
+
+/// Nexus drivers have bus ops, and nodes without a driver have no ops.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn driver_ops() -> Result<()> {
+    use crate::DriverOps;
+
+    let devs = crate::get_devices(false)?;
+    let pci = &devs[&DeviceKey::parse("pci@0,0")];
+    assert!(pci.driver_ops.contains(DriverOps::BUS));
+    for dev in devs.values().filter(|d| d.driver_name.is_none()) {
+        assert_eq!(dev.driver_ops, DriverOps::empty());
+    }
+    Ok(())
+}
+
+/// Re-fetch a single device by its devfs path.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn refetch_cpu() -> Result<()> {
+    let devs = crate::get_devices(false)?;
+    let cpu = &devs[&DeviceKey::parse("cpu@0")];
+    let path = cpu.devfs_path.as_deref().expect("cpu has a devfs path");
+
+    let again = crate::get_device(path, false)?.expect("cpu still exists");
+    assert_eq!(again.node_name, "cpu");
+    assert_eq!(again.instance, cpu.instance);
+    assert_eq!(again.devfs_path.as_deref(), Some(path));
+
+    assert!(crate::get_device("/no-such-device@0", false)?.is_none());
+
+    Ok(())
+}
+
+/// Snapshot failures are classified by errno.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn classify_snapshot_errors() {
+    use crate::sys::snapshot_error;
+    use std::io::Error;
+
+    assert!(matches!(
+        snapshot_error(Error::from_raw_os_error(13), "/"),
+        DevInfoError::PermissionDenied
+    ));
+    assert!(matches!(
+        snapshot_error(Error::from_raw_os_error(12), "/"),
+        DevInfoError::OutOfMemory
+    ));
+    match snapshot_error(Error::from_raw_os_error(6), "/pci@7,0") {
+        DevInfoError::NotFound(path) => assert_eq!(path, "/pci@7,0"),
+        e => panic!("unexpected error {:?}", e),
+    }
+    assert!(matches!(
+        snapshot_error(Error::from_raw_os_error(22), "/"),
+        DevInfoError::Io(_)
+    ));
+}
+
+/// Decode a hand-built idprom and check the derived fields.
+#[test]
+fn decode_idprom() {
+    let mut bytes = vec![
+        0x01, 0x83, 0x08, 0x00, 0x20, 0xaa, 0xbb, 0xcc, 0x3b, 0x9a, 0xca, 0x00,
+        0x12, 0x34, 0x56,
+    ];
+    let xsum = bytes.iter().fold(0u8, |acc, b| acc ^ b);
+    bytes.push(xsum);
+    bytes.extend_from_slice(&[0u8; 16]);
+
+    let idprom = crate::parse_idprom(&bytes).expect("valid idprom");
+    assert_eq!(idprom.machine, 0x83);
+    assert_eq!(idprom.ether, [0x08, 0x00, 0x20, 0xaa, 0xbb, 0xcc]);
+    assert_eq!(idprom.date, 1_000_000_000);
+    assert_eq!(idprom.serial, 0x123456);
+    assert_eq!(idprom.host_id(), 0x83123456);
+    assert!(idprom.checksum_valid());
+
+    // truncated data and unknown formats are rejected
+    assert!(crate::parse_idprom(&bytes[..8]).is_none());
+    bytes[0] = 2;
+    assert!(crate::parse_idprom(&bytes).is_none());
+}
+
+/// Check that each kind of mismatch against a golden property set is found.
+#[test]
+fn expected_props() {
+    let mut dev = DeviceInfo::new();
+    dev.props
+        .insert("vendor-id".to_owned(), DiPropValue::Ints(vec![0x8086]));
+    dev.props
+        .insert("device-id".to_owned(), DiPropValue::Ints(vec![0x1533]));
+    dev.props.insert(
+        "model".to_owned(),
+        DiPropValue::Strings(vec!["Ethernet controller".to_owned()]),
+    );
+
+    let mut expected = BTreeMap::new();
+    expected.insert("vendor-id".to_owned(), DiPropValue::Ints(vec![0x8086]));
+    assert!(dev.matches_expected(&expected).is_empty());
+
+    expected.insert("device-id".to_owned(), DiPropValue::Ints(vec![0x1539]));
+    expected.insert("model".to_owned(), DiPropValue::Boolean(true));
+    expected.insert("subsystem-id".to_owned(), DiPropValue::Ints(vec![0]));
+    assert_eq!(
+        dev.matches_expected(&expected),
+        vec![
+            Mismatch::ValueDiffers {
+                name: "device-id",
+                expected: &expected["device-id"],
+                actual: &dev.props["device-id"],
+            },
+            Mismatch::TypeDiffers {
+                name: "model",
+                expected: &expected["model"],
+                actual: &dev.props["model"],
+            },
+            Mismatch::Missing {
+                name: "subsystem-id"
+            },
+        ]
+    );
+}
+
+/// Parse devfs leaves with and without unit addresses.
+#[test]
+fn parse_device_key() {
+    let key = DeviceKey::parse("cpu@0");
+    assert_eq!(key.node_name, "cpu");
+    assert_eq!(key.unit_address.as_deref(), Some("0"));
+
+    let key = DeviceKey::parse("/pci@0,0/pci8086,1@1f,2");
+    assert_eq!(key.node_name, "pci8086,1");
+    assert_eq!(key.unit_address.as_deref(), Some("1f,2"));
+
+    let key = DeviceKey::parse("pseudo");
+    assert_eq!(key.node_name, "pseudo");
+    assert_eq!(key.unit_address, None);
+
+    let key = DeviceKey::parse("fw@");
+    assert_eq!(key.node_name, "fw");
+    assert_eq!(key.unit_address, None);
+}
+
+/// Borrowed values copy out into the owned equivalents.
+#[test]
+fn borrowed_prop_values() {
+    use crate::{DiPropValueRef, PropStrings};
+
+    let strings = PropStrings::new(b"SCSI\0pci1af4,1\0");
+    assert_eq!(strings.len(), 2);
+    let first = DiPropValueRef::Strings(strings).as_cstr();
+    assert_eq!(first.map(|s| s.to_bytes()), Some(&b"SCSI"[..]));
+    assert_eq!(
+        DiPropValueRef::Strings(strings).to_value(),
+        DiPropValue::Strings(vec!["SCSI".to_owned(), "pci1af4,1".to_owned()])
+    );
+    assert!(PropStrings::new(b"").is_empty());
+
+    let ints = [0x2800, 0];
+    assert_eq!(
+        DiPropValueRef::Ints(&ints).to_value(),
+        DiPropValue::Ints(vec![0x2800, 0])
+    );
+    assert_eq!(DiPropValueRef::Ints(&ints).as_cstr(), None);
+}
+
+/// The borrowing walk sees the same properties as the copying one.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn borrowed_walk_matches() -> Result<()> {
+    let mut borrowed = BTreeMap::new();
+    let mut drivers = BTreeMap::new();
+    crate::for_each_device_ref(false, |key, props| {
+        let driver = props.driver_name().map(|s| s.to_str().unwrap());
+        drivers.insert(key.clone(), driver.map(str::to_owned));
+        let mut values = BTreeMap::new();
+        for (name, value) in props.iter() {
+            values
+                .entry(name.to_owned())
+                .or_insert_with(|| value.to_value());
+        }
+        borrowed.insert(key.clone(), values);
+        std::ops::ControlFlow::Continue(())
+    })?;
+
+    let copied = crate::get_devices(false)?;
+    assert!(borrowed.keys().eq(copied.keys()));
+    for (key, info) in copied {
+        assert_eq!(borrowed[&key], info.props);
+        assert_eq!(drivers[&key], info.driver_name);
+    }
+
+    Ok(())
+}
+
+/// The borrowed prom view sees the same prom properties as the copying
+/// walk.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn borrowed_prom_matches() -> Result<()> {
+    if !crate::can_fetch_prom() {
+        return Ok(());
+    }
+
+    let mut borrowed = BTreeMap::new();
+    crate::for_each_device_ref(true, |key, props| {
+        let prom: BTreeMap<_, _> = props
+            .prom_iter()
+            .map(|(name, bytes)| (name.to_owned(), bytes.to_vec()))
+            .collect();
+        for (name, bytes) in &prom {
+            assert_eq!(props.get_prom(name), Some(bytes.as_slice()));
+        }
+        borrowed.insert(key.clone(), prom);
+        std::ops::ControlFlow::Continue(())
+    })?;
+
+    let copied = crate::get_devices(true)?;
+    for (key, info) in copied {
+        assert_eq!(borrowed[&key], info.prom_props);
+    }
+
+    Ok(())
+}
+
+/// Devices with identical property sets collapse in a hash set, and keys
+/// can be hashed.
+#[test]
+fn hash_props() {
+    use std::collections::HashSet;
+
+    let mut a = DeviceInfo::new();
+    a.props
+        .insert("vendor-id".to_owned(), DiPropValue::Ints(vec![0x8086]));
+    let b = a.clone();
+    let mut c = a.clone();
+    c.props
+        .insert("vendor-id".to_owned(), DiPropValue::Int64s(vec![0x8086]));
+
+    let sets: HashSet<_> = [&a, &b, &c].iter().map(|d| &d.props).collect();
+    assert_eq!(sets.len(), 2);
+
+    let keys: HashSet<_> = ["pci@0,0", "pci@0,0", "pci@1,0"]
+        .map(DeviceKey::parse)
+        .into();
+    assert_eq!(keys.len(), 2);
+}
+
+/// Base addresses come from the first non-config entry, and `reg` entries
+/// only count when they hold absolute addresses.
+#[test]
+fn base_addresses() {
+    fn dev(name: &str, cells: &[u32]) -> DeviceInfo {
+        let mut dev = DeviceInfo::new();
+        let cells = cells.iter().map(|x| *x as i32).collect();
+        dev.props.insert(name.to_owned(), DiPropValue::Ints(cells));
+        dev
+    }
+
+    let config = [0x0000_0800, 0, 0, 0, 0];
+    let mem32 = [0x8200_0810, 0, 0xfe00_0000, 0, 0x4000];
+    let mem64 = [0x8300_0818, 0x1, 0x2000_0000, 0, 0x10_0000];
+    let relocatable = [0x0200_0810, 0, 0, 0, 0x4000];
+
+    let assigned = dev("assigned-addresses", &[config, mem32].concat());
+    assert_eq!(assigned.base_address(), Some(0xfe00_0000));
+    assert_eq!(dev("assigned-addresses", &config).base_address(), None);
+    assert_eq!(
+        dev("assigned-addresses", &mem64).base_address(),
+        Some(0x1_2000_0000)
+    );
+    assert_eq!(
+        dev("reg", &[config, relocatable].concat()).base_address(),
+        None
+    );
+    assert_eq!(dev("reg", &mem32).base_address(), Some(0xfe00_0000));
+}
+
+/// Query flags add to the subtree and properties rather than replacing them.
+#[test]
+fn query_flags() {
+    use crate::{DeviceQuery, SnapshotFlags};
+
+    assert_eq!(DeviceQuery::new().flags, SnapshotFlags::CPYALL);
+    let query = DeviceQuery::new()
+        .root("/pci@0,0")
+        .driver("nvme")
+        .fetch_prom(true)
+        .flags(SnapshotFlags::MINOR);
+    assert_eq!(query.flags, SnapshotFlags::CPYALL);
+    let query = DeviceQuery::new().flags(SnapshotFlags::PATH);
+    assert_eq!(
+        query.flags,
+        SnapshotFlags::SUBTREE | SnapshotFlags::PROP | SnapshotFlags::PATH
+    );
+}
+
+/// Restricting prom properties implies fetching them.
+#[test]
+fn query_prom_props() {
+    use crate::DeviceQuery;
+
+    assert_eq!(DeviceQuery::new().prom_props, None);
+    let query = DeviceQuery::new().only_prom_props(&["model", "reg"]);
+    assert!(query.fetch_prom);
+    assert_eq!(
+        query.prom_props,
+        Some(vec!["model".to_string(), "reg".to_string()])
+    );
+}
+
+/// The prom skip predicate is kept as given, and shown only by presence.
+#[test]
+fn query_prom_skip() {
+    use crate::DeviceQuery;
+
+    assert!(DeviceQuery::new().prom_skip.is_none());
+    let query = DeviceQuery::new()
+        .fetch_prom(true)
+        .prom_skip_predicate(|key| key.node_name == "pseudo");
+    let skip = query.prom_skip.as_ref().unwrap();
+    let key = |name: &str| DeviceKey {
+        node_name: name.to_string(),
+        unit_address: None,
+    };
+    assert!(skip(&key("pseudo")));
+    assert!(!skip(&key("pci")));
+    assert!(format!("{:?}", query).contains("prom_skip: true"));
+}
+
+/// A property can be read from one list even where another list's value
+/// shadows it.
+#[test]
+fn props_by_source() {
+    use crate::{PropSource, ShadowedProp};
+
+    let mut dev = DeviceInfo::new();
+    dev.props
+        .insert("name".to_string(), DiPropValue::Ints(vec![1]));
+    dev.prop_sources
+        .insert("name".to_string(), PropSource::Driver);
+    dev.shadowed_props.push(ShadowedProp {
+        name: "name".to_string(),
+        source: Some(PropSource::System),
+        value: DiPropValue::Ints(vec![2]),
+    });
+
+    assert_eq!(
+        dev.prop_from("name", PropSource::Driver),
+        Some(&DiPropValue::Ints(vec![1]))
+    );
+    assert_eq!(
+        dev.prop_from("name", PropSource::System),
+        Some(&DiPropValue::Ints(vec![2]))
+    );
+    assert_eq!(dev.prop_from("name", PropSource::Hardware), None);
+    assert_eq!(dev.prop_from("other", PropSource::Driver), None);
+}
+
+/// Summaries count devices by driver, falling back to the binding name, and
+/// by inferred class.
+#[test]
+fn summarize_devices() {
+    use crate::DeviceClass;
+
+    let mut devs = BTreeMap::new();
+    let mut nvme = DeviceInfo::new();
+    nvme.driver_name = Some("nvme".to_owned());
+    nvme.props
+        .insert("class-code".to_owned(), DiPropValue::Ints(vec![0x01_08_02]));
+    devs.insert(DeviceKey::parse("pci144d,a808@0"), nvme.clone());
+    devs.insert(DeviceKey::parse("pci144d,a808@1"), nvme);
+    let mut cpu = DeviceInfo::new();
+    cpu.node_name = "cpu".to_owned();
+    cpu.binding_name = Some("cpu".to_owned());
+    devs.insert(DeviceKey::parse("cpu@0"), cpu);
+    devs.insert(DeviceKey::parse("fw@0"), DeviceInfo::new());
+
+    let summary = crate::summarize(&devs);
+    assert_eq!(summary.total, 4);
+    assert_eq!(summary.drivers_by_count(), vec![("nvme", 2), ("cpu", 1)]);
+    assert_eq!(summary.no_driver, 1);
+    assert_eq!(summary.by_class[&DeviceClass::Storage], 2);
+    assert_eq!(summary.by_class[&DeviceClass::Cpu], 1);
+    assert_eq!(summary.by_class[&DeviceClass::Pseudo], 1);
+}
+
+/// Leaves-only queries are opt-in.
+#[test]
+fn query_leaves_only() {
+    use crate::DeviceQuery;
+
+    assert!(!DeviceQuery::new().leaves_only);
+    assert!(DeviceQuery::new().leaves_only(true).leaves_only);
+    assert!(
+        !DeviceQuery::new()
+            .leaves_only(true)
+            .leaves_only(false)
+            .leaves_only
+    );
+}
+
+/// Device classes are guessed from `device_type`, then `class-code`, then
+/// the node name, then the node class.
+#[test]
+fn device_classes() {
+    use crate::{DeviceClass, NodeClass};
+
+    let cases = [
+        (
+            "device_type",
+            DiPropValue::Strings(vec!["cpu".to_owned()]),
+            DeviceClass::Cpu,
+        ),
+        (
+            "device_type",
+            DiPropValue::Strings(vec!["memory".to_owned()]),
+            DeviceClass::Memory,
+        ),
+        (
+            "device_type",
+            DiPropValue::Strings(vec!["network".to_owned()]),
+            DeviceClass::Network,
+        ),
+        (
+            "device_type",
+            DiPropValue::Strings(vec!["scsi".to_owned()]),
+            DeviceClass::Storage,
+        ),
+        (
+            "device_type",
+            DiPropValue::Strings(vec!["pciex".to_owned()]),
+            DeviceClass::Bus,
+        ),
+        (
+            "class-code",
+            DiPropValue::Ints(vec![0x010802]),
+            DeviceClass::Storage,
+        ),
+        (
+            "class-code",
+            DiPropValue::Ints(vec![0x020000]),
+            DeviceClass::Network,
+        ),
+        (
+            "class-code",
+            DiPropValue::Ints(vec![0x050000]),
+            DeviceClass::Memory,
+        ),
+        (
+            "class-code",
+            DiPropValue::Ints(vec![0x060400]),
+            DeviceClass::Bus,
+        ),
+        (
+            "class-code",
+            DiPropValue::Ints(vec![0x0c0330]),
+            DeviceClass::Bus,
+        ),
+        (
+            "class-code",
+            DiPropValue::Ints(vec![0x0b4000]),
+            DeviceClass::Cpu,
+        ),
+        (
+            "class-code",
+            DiPropValue::Ints(vec![0x030000]),
+            DeviceClass::Other,
+        ),
+    ];
+    for (name, value, class) in cases {
+        let mut dev = DeviceInfo::new();
+        dev.node_class = NodeClass::Prom;
+        dev.props.insert(name.to_owned(), value.clone());
+        assert_eq!(dev.device_class(), class, "{} {:?}", name, value);
+    }
+
+    let cases = [
+        ("cpu", NodeClass::Prom, DeviceClass::Cpu),
+        ("memory", NodeClass::Prom, DeviceClass::Memory),
+        ("zfs", NodeClass::Pseudo, DeviceClass::Pseudo),
+        ("pci1af4,1", NodeClass::Prom, DeviceClass::Other),
+    ];
+    for (node_name, node_class, class) in cases {
+        let mut dev = DeviceInfo::new();
+        dev.node_name = node_name.to_owned();
+        dev.node_class = node_class;
+        assert_eq!(dev.device_class(), class, "{}", node_name);
+    }
+}
+
+/// Slot numbers come from `physical-slot#` or its legacy name, and are never
+/// negative.
+#[test]
+fn physical_slots() {
+    fn slot(name: &str, value: i32) -> Option<u32> {
+        let mut dev = DeviceInfo::new();
+        dev.props
+            .insert(name.to_owned(), DiPropValue::Ints(vec![value]));
+        dev.physical_slot()
+    }
+
+    assert_eq!(slot("physical-slot#", 3), Some(3));
+    assert_eq!(slot("physical-slot", 7), Some(7));
+    assert_eq!(slot("physical-slot#", -1), None);
+    assert_eq!(DeviceInfo::new().physical_slot(), None);
+}
+
+/// Slot names are decoded from their mask and packed strings, and give the
+/// physical slot when no slot number property does, as does a hotplug
+/// connector name.
+#[test]
+fn slot_names() {
+    use crate::{HotplugConnector, HotplugState};
+    use std::convert::TryInto;
+
+    fn names(mask: u32, names: &[u8]) -> DeviceInfo {
+        let mut bytes = mask.to_ne_bytes().to_vec();
+        bytes.extend_from_slice(names);
+        let mut dev = DeviceInfo::new();
+        dev.props
+            .insert("slot-names".to_owned(), DiPropValue::Bytes(bytes));
+        dev
+    }
+
+    let dev = names(0b1, b"Slot3\0");
+    assert_eq!(dev.slot_names(), vec![(0, "Slot3".to_owned())]);
+    assert_eq!(dev.physical_slot(), Some(3));
+
+    let dev = names(0b1010, b"pcie6\0pcie7\0");
+    assert_eq!(
+        dev.slot_names(),
+        vec![(1, "pcie6".to_owned()), (3, "pcie7".to_owned())]
+    );
+    assert_eq!(dev.physical_slot(), Some(6));
+
+    // Bits without a name to go with them are dropped.
+    assert_eq!(
+        names(0b11, b"a1\0").slot_names(),
+        vec![(0, "a1".to_owned())]
+    );
+    assert_eq!(names(0b1, b"").slot_names(), vec![]);
+    assert_eq!(names(0b1, b"Slot\0").physical_slot(), None);
+
+    // The property may also be published as ints, in memory order.
+    let mut packed = 0b1u32.to_ne_bytes().to_vec();
+    packed.extend_from_slice(b"S12\0");
+    let ints = packed
+        .chunks(4)
+        .map(|c| i32::from_ne_bytes(c.try_into().unwrap()))
+        .collect();
+    let mut dev = DeviceInfo::new();
+    dev.props
+        .insert("slot-names".to_owned(), DiPropValue::Ints(ints));
+    assert_eq!(dev.physical_slot(), Some(12));
+
+    let mut dev = DeviceInfo::new();
+    dev.hotplug.push(HotplugConnector {
+        name: "pcie5".to_owned(),
+        state: HotplugState::Enabled,
+    });
+    assert_eq!(dev.physical_slot(), Some(5));
+
+    // An explicit slot number wins over names.
+    dev.props
+        .insert("physical-slot#".to_owned(), DiPropValue::Ints(vec![2]));
+    assert_eq!(dev.physical_slot(), Some(2));
+}
+
+/// SR-IOV roles are recognized from the properties each kind of function
+/// carries.
+#[test]
+fn sriov_roles() {
+    use crate::SriovRole;
+
+    let cases = [
+        ("vf", Some(SriovRole::Virtual)),
+        ("pf", Some(SriovRole::Physical)),
+        ("iov-busnum", Some(SriovRole::Physical)),
+        ("vendor-id", None),
+    ];
+    for (name, role) in cases {
+        let mut dev = DeviceInfo::new();
+        dev.props
+            .insert(name.to_owned(), DiPropValue::Ints(vec![1]));
+        assert_eq!(dev.sriov_role(), role, "{}", name);
+    }
+}
+
+/// Version-like properties are found by name and joined, and non-string
+/// values are skipped.
+#[test]
+fn version_props() {
+    let mut dev = DeviceInfo::new();
+    let strings = |xs: &[&str]| {
+        DiPropValue::Strings(xs.iter().map(|x| x.to_string()).collect())
+    };
+    dev.props.insert("version".to_owned(), strings(&["1.0"]));
+    dev.props
+        .insert("firmware-version".to_owned(), strings(&["2.1", "2.0"]));
+    dev.props.insert("fw_version".to_owned(), strings(&["3"]));
+    dev.props
+        .insert("hw-version".to_owned(), DiPropValue::Ints(vec![4]));
+    dev.props.insert("versionless".to_owned(), strings(&["no"]));
+
+    let versions = dev.versions();
+    assert_eq!(versions.len(), 3);
+    assert_eq!(versions["version"], "1.0");
+    assert_eq!(versions["firmware-version"], "2.1, 2.0");
+    assert_eq!(versions["fw_version"], "3");
+}
+
+/// Minor node types match along the colon-separated hierarchy.
+#[test]
+fn minor_types() {
+    use crate::{MinorNode, SpecType};
+
+    let minor = |node_type: Option<&str>| MinorNode {
+        name: "a".to_owned(),
+        node_type: node_type.map(str::to_owned),
+        spec_type: SpecType::Block,
+        minor_path: None,
+    };
+
+    let cdrom = minor(Some("ddi_block:cdrom"));
+    assert!(cdrom.is_type("ddi_block"));
+    assert!(cdrom.is_type("ddi_block:cdrom"));
+    assert!(!cdrom.is_type("ddi_block:channel"));
+    assert!(!cdrom.is_type("ddi_network"));
+    assert!(minor(Some("ddi_block")).is_type("ddi_block"));
+    assert!(!minor(Some("ddi_blockdev")).is_type("ddi_block"));
+    assert!(!minor(None).is_type("ddi_block"));
+}
+
+/// Byte properties decode in the byte order asked for.
+#[test]
+fn byte_order() {
+    let word = DiPropValue::Bytes(vec![0x12, 0x34, 0x56, 0x78]);
+    assert_eq!(word.as_u32_be(), Some(0x1234_5678));
+    assert_eq!(word.as_u32_le(), Some(0x7856_3412));
+
+    let words = DiPropValue::Bytes(vec![0, 0, 0, 1, 2, 0, 0, 0]);
+    assert_eq!(words.as_u32_array_be(), Some(vec![1, 0x0200_0000]));
+    assert_eq!(words.as_u32_array_le(), Some(vec![0x0100_0000, 2]));
+    assert_eq!(words.as_u32_be(), None);
+
+    assert_eq!(DiPropValue::Bytes(vec![1, 2, 3]).as_u32_array_be(), None);
+    assert_eq!(DiPropValue::Ints(vec![1]).as_u32_le(), None);
+    assert_eq!(DiPropValue::Bytes(vec![]).as_u32_array_le(), Some(vec![]));
+}
+
+/// Labels fall back from `model` to compatible names, the binding name and
+/// finally the node's name and address.
+#[test]
+fn device_labels() {
+    let mut dev = DeviceInfo::new();
+    dev.node_name = "pci1af4,1".to_owned();
+    assert_eq!(dev.label(), "pci1af4,1");
+
+    dev.devfs_path = Some("/pci@0,0/pci1af4,1@5".to_owned());
+    assert_eq!(dev.label(), "pci1af4,1@5");
+
+    dev.binding_name = Some("pci1af4,1".to_owned());
+    assert_eq!(dev.label(), "pci1af4,1");
+
+    dev.compatible =
+        vec!["pci1af4,1.1af4.1".to_owned(), "pci1af4,1".to_owned()];
+    assert_eq!(dev.label(), "pci1af4,1.1af4.1");
+
+    dev.props.insert(
+        "model".to_owned(),
+        DiPropValue::Strings(vec!["Virtio network device".to_owned()]),
+    );
+    assert_eq!(dev.label(), "Virtio network device");
+}
+
+/// Only single-element integer properties are scalars.
+#[test]
+fn scalar_ints() {
+    assert_eq!(
+        DiPropValue::Ints(vec![0x8086]).as_scalar_int(),
+        Some(0x8086)
+    );
+    assert_eq!(DiPropValue::Ints(vec![0x2800, 0]).as_scalar_int(), None);
+    assert_eq!(DiPropValue::Ints(vec![]).as_scalar_int(), None);
+    assert_eq!(DiPropValue::Int64s(vec![1]).as_scalar_int(), None);
+
+    assert!(DiPropValue::Ints(vec![7]).matches_int(7));
+    assert!(!DiPropValue::Ints(vec![7, 7]).matches_int(7));
+}
+
+/// Convert single-valued properties to scalars, and refuse anything else.
+#[test]
+fn prop_value_conversions() {
+    use std::convert::{TryFrom, TryInto};
+
+    let id: i32 = (&DiPropValue::Ints(vec![0x1234])).try_into().unwrap();
+    assert_eq!(id, 0x1234);
+    assert_eq!(i64::try_from(&DiPropValue::Ints(vec![-1])).unwrap(), -1);
+    assert_eq!(
+        i64::try_from(&DiPropValue::Int64s(vec![1 << 40])).unwrap(),
+        1 << 40
+    );
+    assert_eq!(
+        String::try_from(&DiPropValue::Strings(vec!["nvme".to_owned()]))
+            .unwrap(),
+        "nvme"
+    );
+    assert!(bool::try_from(&DiPropValue::Boolean(true)).unwrap());
+
+    assert!(matches!(
+        i32::try_from(&DiPropValue::Ints(vec![1, 2])),
+        Err(DevInfoError::WrongType("i32"))
+    ));
+    assert!(matches!(
+        i32::try_from(&DiPropValue::Int64s(vec![1])),
+        Err(DevInfoError::WrongType("i32"))
+    ));
+    assert!(String::try_from(&DiPropValue::Strings(vec![])).is_err());
+    assert!(bool::try_from(&DiPropValue::Bytes(vec![1])).is_err());
+}
+
+/// Assert that unit addresses order numerically rather than lexically.
+#[test]
+fn device_key_order() {
+    let mut keys: Vec<DeviceKey> = [
+        "pci@10",
+        "pci@2",
+        "pci@1f,2",
+        "pci@1f,10",
+        "pci@1",
+        "pci",
+        "cpu@a",
+        "pci@01",
+    ]
+    .iter()
+    .map(|k| DeviceKey::parse(k))
+    .collect();
+    keys.sort();
+
+    let keys: Vec<String> = keys.iter().map(|k| k.to_string()).collect();
+    assert_eq!(
+        keys,
+        [
+            "cpu@a",
+            "pci",
+            "pci@01",
+            "pci@1",
+            "pci@2",
+            "pci@10",
+            "pci@1f,2",
+            "pci@1f,10",
+        ]
+    );
+}
+
+/// Round-trip a device map through JSON.
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    let mut dev = DeviceInfo::new();
+    dev.props
+        .insert("reg".to_owned(), DiPropValue::Ints(vec![0x2800, 0]));
+    dev.props
+        .insert("size".to_owned(), DiPropValue::Int64s(vec![0x2800, 0]));
+    dev.prom_props.insert("name".to_owned(), b"pci\0".to_vec());
+    dev.nodeid = crate::NodeId::Prom(0x1a2b);
+
+    let mut devs = BTreeMap::new();
+    devs.insert(DeviceKey::parse("pci1af4,a@5"), dev);
+
+    let json = serde_json::to_string(&devs).unwrap();
+    let back: BTreeMap<DeviceKey, DeviceInfo> =
+        serde_json::from_str(&json).unwrap();
+    let dev = &back[&DeviceKey::parse("pci1af4,a@5")];
+    assert_eq!(dev.props["reg"], DiPropValue::Ints(vec![0x2800, 0]));
+    assert_eq!(dev.props["size"], DiPropValue::Int64s(vec![0x2800, 0]));
+    assert_eq!(dev.prom_props["name"], b"pci\0".to_vec());
+    assert_eq!(dev.nodeid, crate::NodeId::Prom(0x1a2b));
+}
+
+/// Single integers are written without an array, and every value reads
+/// back as it was, in either representation.
+#[cfg(feature = "serde")]
+#[test]
+fn compact_serde() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Props {
+        #[serde(with = "crate::compact::map")]
+        props: BTreeMap<String, DiPropValue>,
+    }
+
+    let mut props = BTreeMap::new();
+    props.insert("a".to_owned(), DiPropValue::Ints(vec![5]));
+    props.insert("b".to_owned(), DiPropValue::Ints(vec![5, 6]));
+    props.insert("c".to_owned(), DiPropValue::Int64s(vec![7]));
+    props.insert("d".to_owned(), DiPropValue::Ints(vec![]));
+    props.insert("e".to_owned(), DiPropValue::Bytes(vec![8]));
+    props.insert("f".to_owned(), DiPropValue::Strings(vec!["x".to_owned()]));
+    let props = Props { props };
+
+    let json = serde_json::to_string(&props).unwrap();
+    assert_eq!(
+        json,
+        concat!(
+            r#"{"props":{"a":{"Int":5},"b":{"Ints":[5,6]},"c":{"Int64":7},"#,
+            r#""d":{"Ints":[]},"e":{"Bytes":[8]},"f":{"Strings":["x"]}}}"#,
+        )
+    );
+    let back: Props = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.props, props.props);
+
+    let plain = serde_json::to_string(&props.props).unwrap();
+    let back: Props =
+        serde_json::from_str(&format!(r#"{{"props":{}}}"#, plain)).unwrap();
+    assert_eq!(back.props, props.props);
+}
+
+/// Save a device map and load it back, and refuse a file of another
+/// version.
+#[cfg(feature = "serde")]
+#[test]
+fn save_and_load() {
+    let mut dev = DeviceInfo::new();
+    dev.props
+        .insert("device-id".to_owned(), DiPropValue::Ints(vec![0x1533]));
+    dev.compatible = vec!["pciex8086,1533".to_owned()];
+    dev.minors.push(crate::MinorNode {
+        name: "igb0".to_owned(),
+        node_type: Some("ddi_network".to_owned()),
+        spec_type: crate::SpecType::Char,
+        minor_path: None,
+    });
+
+    let mut devs = BTreeMap::new();
+    devs.insert(DeviceKey::parse("pci8086,1533@0"), dev);
+
+    let mut buf = Vec::new();
+    crate::save_devices(&devs, &mut buf).unwrap();
+    assert_eq!(crate::load_devices(buf.as_slice()).unwrap(), devs);
+
+    // Files saved before fields were added without a version bump still
+    // load, with those fields defaulted.
+    let mut saved: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+    let dev = &mut saved["devices"]["pci8086,1533@0"];
+    for field in ["links", "hotplug", "driver_ops", "multipath_role"] {
+        dev.as_object_mut().unwrap().remove(field).unwrap();
+    }
+    let minor = dev["minors"][0].as_object_mut().unwrap();
+    minor.remove("minor_path").unwrap();
+    let loaded = crate::load_devices(saved.to_string().as_bytes()).unwrap();
+    assert_eq!(loaded, devs);
+
+    // An older file whose devices lack fields added since is rejected for
+    // its version.
+    let other = br#"{"version": 0, "devices": {"pci@0,0": {"props": {}}}}"#;
+    match crate::load_devices(&other[..]) {
+        Err(DevInfoError::Io(e)) => {
+            assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+            assert!(e.to_string().contains("version 0"), "{}", e);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+/// Check the prom value heuristics for strings, integers, and raw bytes.
+#[test]
+fn decode_prom_values() {
+    use crate::DiPromValue;
+
+    assert_eq!(
+        DiPromValue::decode(b"pci1af4,1\0pciclass,010000\0"),
+        DiPromValue::Strings(vec![
+            "pci1af4,1".to_owned(),
+            "pciclass,010000".to_owned()
+        ])
+    );
+    assert_eq!(
+        DiPromValue::decode(&[0, 0, 0, 2, 0, 0, 0x10, 0]),
+        DiPromValue::Ints(vec![2, 0x1000])
+    );
+    assert_eq!(
+        DiPromValue::decode(&[1, 2, 3]),
+        DiPromValue::Bytes(vec![1, 2, 3])
+    );
+    assert_eq!(DiPromValue::decode(&[]), DiPromValue::Bytes(vec![]));
+}
+
+/// Render warnings with whatever detail they carry.
+#[test]
+fn display_warning() {
+    use crate::DevInfoWarning;
+
+    let warning = DevInfoWarning {
+        node: DeviceKey::parse("pci8086,1533@0"),
+        prop: Some("vendor-id".to_owned()),
+        errno: None,
+        message: "failed to get ints".to_owned(),
+    };
+    assert_eq!(
+        warning.to_string(),
+        "pci8086,1533@0: vendor-id: failed to get ints"
+    );
+
+    let warning = DevInfoWarning {
+        prop: None,
+        errno: Some(2),
+        message: "di_prom_init failed".to_owned(),
+        ..warning
+    };
+    let e = std::io::Error::from_raw_os_error(2);
+    assert_eq!(
+        warning.to_string(),
+        format!("pci8086,1533@0: di_prom_init failed: {}", e)
+    );
+}
+
+/// Look up prom properties with well-known encodings.
+#[test]
+fn typed_prom_props() {
+    let mut dev = DeviceInfo::new();
+    for (name, bytes) in [
+        ("model", &b"Ethernet controller\0"[..]),
+        ("#address-cells", &[0, 0, 0, 3][..]),
+        ("compatible", &b"pci1af4,1\0pciclass,010000\0"[..]),
+        ("reg", &[0, 0, 0, 1, 0, 0, 0, 2][..]),
+    ] {
+        dev.prom_props.insert(name.to_owned(), bytes.to_vec());
+    }
+
+    assert_eq!(
+        dev.prom_prop_string("model").as_deref(),
+        Some("Ethernet controller")
+    );
+    assert_eq!(dev.prom_prop_u32("#address-cells"), Some(3));
+
+    assert_eq!(dev.prom_prop_string("compatible"), None);
+    assert_eq!(dev.prom_prop_string("#address-cells"), None);
+    assert_eq!(dev.prom_prop_u32("reg"), None);
+    assert_eq!(dev.prom_prop_u32("model"), None);
+    assert_eq!(dev.prom_prop_u32("missing"), None);
+}
+
+/// Filter a device map on property values.
+#[test]
+fn filter_by_prop() {
+    use crate::DeviceMapExt;
+
+    let mut devs = BTreeMap::new();
+    for (leaf, vendor, id) in [
+        ("pci8086,1533@0", 0x8086, 0x1533),
+        ("pci8086,1539@1", 0x8086, 0x1539),
+        ("pci1af4,1009@5", 0x1af4, 0x1009),
+    ] {
+        let mut dev = DeviceInfo::new();
+        dev.props
+            .insert("vendor-id".to_owned(), DiPropValue::Ints(vec![vendor]));
+        dev.props
+            .insert("device-id".to_owned(), DiPropValue::Ints(vec![id]));
+        devs.insert(DeviceKey::parse(leaf), dev);
+    }
+
+    let intel: Vec<_> = devs
+        .filter_by_prop("vendor-id", DiPropValue::Ints(vec![0x8086]))
+        .map(|(key, _)| key.to_string())
+        .collect();
+    assert_eq!(intel, vec!["pci8086,1533@0", "pci8086,1539@1"]);
+
+    let igb: Vec<_> = devs
+        .filter_by_prop("vendor-id", DiPropValue::Ints(vec![0x8086]))
+        .filter_by_prop("device-id", DiPropValue::Ints(vec![0x1539]))
+        .map(|(key, _)| key.to_string())
+        .collect();
+    assert_eq!(igb, vec!["pci8086,1539@1"]);
+}
+
+/// Render property values in either radix.
+#[test]
+fn format_radix() {
+    use crate::Radix;
+
+    let v = DiPropValue::Ints(vec![0x10, 255]);
+    assert_eq!(v.format(Radix::Hex), "[10, ff]");
+    assert_eq!(v.format(Radix::Hex), format!("{:x}", v));
+    assert_eq!(format!("{:X}", v), "[10, FF]");
+    assert_eq!(v.format(Radix::Decimal), "[16, 255]");
+    assert_eq!(v.format(Radix::Decimal), v.to_string());
+
+    let v = DiPropValue::Bytes(vec![0x0a]);
+    assert_eq!(v.format(Radix::Hex), "[0a]");
+    assert_eq!(v.format(Radix::Decimal), "[10]");
+}
+
+/// String properties are decoded by count, tolerating non-UTF-8 data.
+#[test]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn read_string_props() {
+    use crate::sys::read_strings;
+
+    let data = b"SCSI\0pci1af4,1\0bad\xff\0trailing\0";
+    let strings = unsafe { read_strings(data.as_ptr() as *const _, 3) };
+    assert_eq!(strings, vec!["SCSI", "pci1af4,1", "bad\u{fffd}"]);
+}
+
+/// Walk a node's descendants depth first.
+#[test]
+fn node_descendants() {
+    use crate::DeviceNode;
+
+    fn node(leaf: &str, children: Vec<DeviceNode>) -> DeviceNode {
+        DeviceNode {
+            key: DeviceKey::parse(leaf),
+            info: DeviceInfo::new(),
+            children,
+        }
+    }
+
+    let root = node(
+        "pci@0,0",
+        vec![
+            node(
+                "pci8086,1@1",
+                vec![node("disk@0", vec![]), node("disk@1", vec![])],
+            ),
+            node("isa@1f", vec![]),
+        ],
+    );
+
+    let names: Vec<String> =
+        root.descendants().map(|n| n.key.to_string()).collect();
+    assert_eq!(names, ["pci8086,1@1", "disk@0", "disk@1", "isa@1f"]);
+
+    let leaves: Vec<String> = root
+        .descendants()
+        .filter(|n| n.is_leaf())
+        .map(|n| n.key.to_string())
+        .collect();
+    assert_eq!(leaves, ["disk@0", "disk@1", "isa@1f"]);
+
+    assert!(root.has_children());
+    assert_eq!(root.children[1].descendants().count(), 0);
+}
+
+/// Plain-text rendering of a device's properties.
+#[test]
+fn display_device_info() {
+    let mut dev = DeviceInfo::new();
+    dev.props
+        .insert("vendor-id".to_owned(), DiPropValue::Ints(vec![0x1af4]));
+    dev.props.insert(
+        "model".to_owned(),
+        DiPropValue::Strings(vec!["Virtio".to_owned()]),
+    );
+    assert_eq!(dev.to_string(), "model: [\"Virtio\"]\nvendor-id: [6900]\n");
+
+    dev.props
+        .insert("#address-cells".to_owned(), DiPropValue::Ints(vec![16]));
+    assert_eq!(
+        dev.display(crate::Radix::Hex).to_string(),
+        "#address-cells: [16]\nmodel: [\"Virtio\"]\nvendor-id: [1af4]\n"
+    );
+}
+
+/// Without libdevinfo every query fails cleanly rather than at link time.
+#[test]
+#[cfg(not(any(target_os = "illumos", target_os = "solaris")))]
+fn unsupported_platform() {
+    assert!(matches!(
+        crate::get_devices(false),
+        Err(DevInfoError::Unsupported)
+    ));
+    assert!(!crate::prom_available());
+    assert!(!crate::can_fetch_prom());
+}
+
+/// The parallel walk finds the same devices as the serial one.
+#[test]
+#[cfg(feature = "parallel")]
+#[cfg(any(target_os = "illumos", target_os = "solaris"))]
+fn parallel_matches_serial() -> Result<()> {
+    let serial = crate::get_devices(false)?;
+    let parallel = crate::get_devices_parallel(false)?;
+    assert!(serial.keys().eq(parallel.keys()));
+    Ok(())
+}
+
+/// A cloned device compares equal until one of its properties changes.
+#[test]
+fn detect_prop_change() {
+    let mut before = DeviceInfo::new();
+    before
+        .props
+        .insert("link-up".to_owned(), DiPropValue::Ints(vec![1]));
+
+    let mut after = before.clone();
+    assert_eq!(before, after);
+
+    after
+        .props
+        .insert("link-up".to_owned(), DiPropValue::Ints(vec![0]));
+    assert_ne!(before, after);
+}
+
+/// Diff two reads of a device's properties.
+#[test]
+fn diff_props() {
+    let mut before = DeviceInfo::new();
+    for (name, x) in [("link-up", 1), ("speed", 1000), ("old", 0)] {
+        before
+            .props
+            .insert(name.to_owned(), DiPropValue::Ints(vec![x]));
+    }
+    let mut after = before.clone();
+    assert!(before.diff(&after).is_empty());
+
+    after.props.remove("old");
+    after
+        .props
+        .insert("link-up".to_owned(), DiPropValue::Ints(vec![0]));
+    after
+        .props
+        .insert("temperature".to_owned(), DiPropValue::Ints(vec![45]));
+
+    let diff = before.diff(&after);
+    assert_eq!(
+        diff.added.into_iter().collect::<Vec<_>>(),
+        [("temperature", &DiPropValue::Ints(vec![45]))]
+    );
+    assert_eq!(
+        diff.removed.into_iter().collect::<Vec<_>>(),
+        [("old", &DiPropValue::Ints(vec![0]))]
+    );
+    assert_eq!(
+        diff.changed.into_iter().collect::<Vec<_>>(),
+        [(
+            "link-up",
+            (&DiPropValue::Ints(vec![1]), &DiPropValue::Ints(vec![0]))
+        )]
+    );
+}
+
+/// Predicates match only when every one of them holds.
+#[test]
+fn match_predicates() {
+    use crate::PropPredicate;
+
+    let mut dev = DeviceInfo::new();
+    dev.props
+        .insert("vendor-id".to_owned(), DiPropValue::Ints(vec![0x8086]));
+    dev.props
+        .insert("device-id".to_owned(), DiPropValue::Ints(vec![0x1533]));
+
+    let vendor =
+        PropPredicate::new("vendor-id", DiPropValue::Ints(vec![0x8086]));
+    let device =
+        PropPredicate::new("device-id", DiPropValue::Ints(vec![0x1533]));
+    let other =
+        PropPredicate::new("device-id", DiPropValue::Ints(vec![0x1539]));
+    let missing = PropPredicate::new("class-code", DiPropValue::Ints(vec![0]));
+
+    assert!(dev.matches(&[]));
+    assert!(dev.matches(&[vendor.clone(), device]));
+    assert!(!dev.matches(&[vendor.clone(), other]));
+    assert!(!dev.matches(&[vendor, missing]));
+}
+
+/// The async wrapper hands back the result of the blocking walk.
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn get_devices_async() {
+    let result = crate::get_devices_async(false).await;
+    assert_eq!(result.is_ok(), crate::get_devices_blocking(false).is_ok());
+}
@@ -0,0 +1,231 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+// Copyright 2022 Oxide Computer Company
+
+//! Stand-ins for the libdevinfo-backed functions on platforms that don't
+//! have libdevinfo. They allow crates that only use this crate's types to
+//! build and test anywhere; every call fails with
+//! [`DevInfoError::Unsupported`].
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::marker::PhantomData;
+use std::ops::ControlFlow;
+
+#[cfg(feature = "private-data")]
+use crate::PrivateData;
+use crate::{
+    DevInfoError, DeviceInfo, DeviceKey, DeviceQuery, DeviceTree, DiPromValue,
+    DiPropType, DiPropValue, DiPropValueRef, MinorNode, PropSource,
+    SnapshotFlags,
+};
+
+type Result<T> = std::result::Result<T, DevInfoError>;
+
+pub fn get_devices(
+    _fetch_prom: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub(crate) fn run_query(
+    _query: &DeviceQuery,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn get_devices_at(
+    _path: &str,
+    _fetch_prom: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn for_each_device<F>(_fetch_prom: bool, _f: F) -> Result<()>
+where
+    F: FnMut(&DeviceKey, &DeviceInfo) -> ControlFlow<()>,
+{
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn count_devices(_fetch_prom: bool) -> Result<usize> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub struct PropsRef<'a> {
+    _snapshot: PhantomData<&'a ()>,
+}
+
+impl<'a> PropsRef<'a> {
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, DiPropValueRef<'a>)> {
+        std::iter::empty()
+    }
+
+    pub fn get(&self, _name: &str) -> Option<DiPropValueRef<'a>> {
+        None
+    }
+
+    pub fn get_from(
+        &self,
+        _name: &str,
+        _source: PropSource,
+    ) -> Option<DiPropValueRef<'a>> {
+        None
+    }
+
+    pub fn get_cstr(&self, _name: &str) -> Option<&'a CStr> {
+        None
+    }
+
+    pub fn driver_name(&self) -> Option<&'a CStr> {
+        None
+    }
+
+    pub fn prom_iter(&self) -> impl Iterator<Item = (&'a str, &'a [u8])> {
+        std::iter::empty()
+    }
+
+    pub fn get_prom(&self, _name: &str) -> Option<&'a [u8]> {
+        None
+    }
+}
+
+pub fn for_each_device_ref<F>(_fetch_prom: bool, _f: F) -> Result<()>
+where
+    F: FnMut(&DeviceKey, &PropsRef<'_>) -> ControlFlow<()>,
+{
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn get_prop_from(
+    _key: &DeviceKey,
+    _name: &str,
+    _source: PropSource,
+) -> Result<Option<DiPropValue>> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn get_devices_by_driver(
+    _driver: &str,
+    _fetch_prom: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn get_device_by_instance(
+    _driver: &str,
+    _instance: i32,
+    _fetch_prom: bool,
+) -> Result<Option<(DeviceKey, DeviceInfo)>> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn get_driver_props(
+    _driver: &str,
+    _props: &[(&str, DiPropType)],
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn get_device_tree(_fetch_prom: bool) -> Result<DeviceTree> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn get_both(
+    _fetch_prom: bool,
+) -> Result<(DeviceTree, BTreeMap<DeviceKey, DeviceInfo>)> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn prom_available() -> bool {
+    false
+}
+
+pub fn can_fetch_prom() -> bool {
+    false
+}
+
+/// # Safety
+///
+/// Always fails on this platform; see the illumos implementation.
+#[cfg(feature = "private-data")]
+pub unsafe fn raw_private_data(
+    _path: &str,
+    _parent_len: usize,
+    _driver_len: usize,
+) -> Result<PrivateData> {
+    Err(DevInfoError::Unsupported)
+}
+
+#[cfg(feature = "parallel")]
+pub fn get_devices_parallel(
+    _fetch_prom: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn get_device(
+    _path: &str,
+    _fetch_prom: bool,
+) -> Result<Option<DeviceInfo>> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn walk_minors<F>(_node_type: Option<&str>, _f: F) -> Result<()>
+where
+    F: FnMut(&DeviceKey, &MinorNode) -> ControlFlow<()>,
+{
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn get_devices_with_paths(
+    _fetch_prom: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub fn get_devices_with(
+    _flags: SnapshotFlags,
+    _fetch_prom: bool,
+) -> Result<BTreeMap<DeviceKey, DeviceInfo>> {
+    Err(DevInfoError::Unsupported)
+}
+
+pub struct PromTree {
+    _private: (),
+}
+
+impl PromTree {
+    pub fn open() -> Result<PromTree> {
+        Err(DevInfoError::Unsupported)
+    }
+
+    pub fn version(&mut self) -> Result<String> {
+        Err(DevInfoError::Unsupported)
+    }
+
+    pub fn root(&mut self) -> Result<Option<i32>> {
+        Err(DevInfoError::Unsupported)
+    }
+
+    pub fn first_child(&mut self, _node: i32) -> Result<Option<i32>> {
+        Err(DevInfoError::Unsupported)
+    }
+
+    pub fn next_sibling(&mut self, _node: i32) -> Result<Option<i32>> {
+        Err(DevInfoError::Unsupported)
+    }
+
+    pub fn next_property(
+        &mut self,
+        _prev: Option<&str>,
+    ) -> Result<Option<String>> {
+        Err(DevInfoError::Unsupported)
+    }
+
+    pub fn property(&mut self, _name: &str) -> Result<Option<DiPromValue>> {
+        Err(DevInfoError::Unsupported)
+    }
+}